@@ -1,3 +1,4 @@
 fn main() {
+    #[cfg(feature = "esp")]
     embuild::espidf::sysenv::output();
 }
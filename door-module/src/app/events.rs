@@ -1,3 +1,5 @@
+use shared_lib::dto::pw_config::DTO_SIZE;
+
 #[derive(Debug, Clone)]
 pub enum ServerRequestType {
     Stop = 0b0001,
@@ -6,11 +8,19 @@ pub enum ServerRequestType {
     OpenFully = 0b1010,
     CloseFully = 0b1110,
 
-    ConfigureCurrentThresholds = 0x10
+    ConfigureCurrentThresholds = 0x10,
+    ResetConfig = 0x11,
+    ClearFault = 0x12,
+
+    MoveToPosition = 0x20,
+
+    Calibrate = 0x30,
+
+    Heartbeat = 0x40,
 }
 
 #[derive(Debug, Clone)]
 pub struct ServerRequest {
     pub request_type: ServerRequestType,
-    pub request_data: [u8; 8]
+    pub request_data: [u8; DTO_SIZE]
 }
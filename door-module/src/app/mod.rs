@@ -1,2 +1 @@
-pub mod state;
 pub mod events;
\ No newline at end of file
@@ -1,3 +0,0 @@
-pub struct AppState {
-  pub random_val: &'static str,
-}
\ No newline at end of file
@@ -0,0 +1,16 @@
+use esp_idf_hal::delay::FreeRtos;
+use esp_idf_svc::systime::EspSystemTime;
+
+use super::traits::Clock;
+
+pub struct SystemClock;
+
+impl Clock for SystemClock {
+    fn now_millis(&self) -> u128 {
+        EspSystemTime::now(&EspSystemTime {}).as_millis()
+    }
+
+    fn delay_ms(&mut self, millis: u32) {
+        FreeRtos::delay_ms(millis);
+    }
+}
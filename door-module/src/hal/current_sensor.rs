@@ -0,0 +1,86 @@
+use shared_lib::units::{Milliamps, Millivolts, MillivoltsPerAmp};
+
+/// Largest current a learned zero offset may correspond to, higher readings with
+/// the relays released are left to the relay supervision
+const MAX_ZERO_OFFSET_CURRENT: Milliamps = Milliamps(300);
+/// Weight of a new reading in the zero offset
+const ZERO_OFFSET_WEIGHT: f32 = 0.1;
+
+#[derive(Debug, Clone, Copy)]
+pub struct CurrentSensorConfig {
+    /// Gain of the sense amplifier
+    pub gain: MillivoltsPerAmp,
+    /// Sense voltage from which on the ADC saturates
+    pub saturation: Millivolts,
+}
+
+/// Motor current derived from a sense voltage
+#[derive(Debug, Clone, Copy, Default)]
+pub struct SensedCurrent {
+    pub current: Milliamps,
+    /// The sense voltage was at the end of the ADC range, the real current is at least `current`
+    pub saturated: bool,
+}
+
+/// Converts the voltage drop across a current sense resistor into a current,
+/// correcting the amplifier offset learned while no current flows.
+pub struct CurrentSensor {
+    config: CurrentSensorConfig,
+    offset_millivolts: Option<f32>,
+}
+
+impl CurrentSensor {
+    pub fn new(config: CurrentSensorConfig) -> CurrentSensor {
+        CurrentSensor {
+            config,
+            offset_millivolts: None,
+        }
+    }
+
+    pub fn set_config(&mut self, config: CurrentSensorConfig) {
+        self.config = config;
+    }
+
+    /// Learned zero current offset, zero until one has been learned
+    pub fn offset(&self) -> Millivolts {
+        Millivolts(self.offset_millivolts.unwrap_or(0.0).round() as u16)
+    }
+
+    pub fn to_current(&self, voltage: Millivolts) -> SensedCurrent {
+        let saturated = voltage >= self.config.saturation;
+        let voltage = voltage.min(self.config.saturation);
+
+        SensedCurrent {
+            current: self
+                .config
+                .gain
+                .checked_current(voltage.saturating_sub(self.offset()))
+                .unwrap_or(Milliamps::MAX),
+            saturated,
+        }
+    }
+
+    /// Sense voltage the given current produces, the inverse of `to_current` up to the saturation
+    pub fn to_voltage(&self, current: Milliamps) -> Millivolts {
+        let voltage = self.config.gain.checked_voltage(current).unwrap_or(Millivolts::MAX);
+
+        voltage.saturating_add(self.offset()).min(self.config.saturation)
+    }
+
+    /// Feeds a sense voltage measured while no current can flow, returns whether it has been accepted as offset
+    pub fn zero(&mut self, voltage: Millivolts) -> bool {
+        let max_offset = self.config.gain.checked_voltage(MAX_ZERO_OFFSET_CURRENT).unwrap_or(Millivolts::MAX);
+
+        if voltage > max_offset {
+            return false;
+        }
+
+        let millivolts = voltage.0 as f32;
+        self.offset_millivolts = Some(match self.offset_millivolts {
+            Some(offset) => offset + (millivolts - offset) * ZERO_OFFSET_WEIGHT,
+            None => millivolts,
+        });
+
+        true
+    }
+}
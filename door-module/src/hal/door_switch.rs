@@ -0,0 +1,22 @@
+use esp_idf_hal::gpio::{Gpio18, Input, PinDriver, Pull};
+use esp_idf_sys::EspError;
+
+use super::traits::{DoorSwitchInput, HalError};
+
+/// Door contact pulling the input low while the door is open
+pub struct DoorSwitchPin {
+    pin: PinDriver<'static, Gpio18, Input>,
+}
+
+impl DoorSwitchInput for DoorSwitchPin {
+    fn is_door_open(&mut self) -> Result<bool, HalError> {
+        Ok(self.pin.is_low())
+    }
+}
+
+pub fn prepare_door_switch(pin: Gpio18) -> Result<DoorSwitchPin, EspError> {
+    let mut pin = PinDriver::input(pin)?;
+    pin.set_pull(Pull::Up)?;
+
+    Ok(DoorSwitchPin { pin })
+}
@@ -1,65 +1,152 @@
+use std::time::Duration;
+
 use esp_idf_hal::{
-    adc::{
-      attenuation::{self}, 
-      config::Config, 
-      AdcChannelDriver, 
-      AdcDriver, 
-      ADC1
-    },
-    gpio::{Gpio2, Gpio3},
+    adc::{attenuation, AdcContConfig, AdcContDriver, AdcMeasurement, Attenuated, EmptyAdcChannels, ADC1},
+    delay::TickType,
+    gpio::{ADCPin, Gpio2, Gpio3, Gpio4, Gpio5},
+    units::Hertz,
+};
+use esp_idf_sys::{
+    adc_bitwidth_t_ADC_BITWIDTH_12, adc_cali_create_scheme_curve_fitting, adc_cali_curve_fitting_config_t,
+    adc_atten_t, adc_cali_delete_scheme_curve_fitting, adc_cali_handle_t, adc_cali_raw_to_voltage, adc_channel_t,
+    adc_unit_t_ADC_UNIT_1, esp, EspError,
 };
-use esp_idf_sys::EspError;
+use shared_lib::sampling::stream::{AdcStream, StreamSample};
+
+pub const CLOSING_CURRENT_SENSE_CHANNEL: usize = 0;
+pub const OPENING_CURRENT_SENSE_CHANNEL: usize = 1;
+pub const WINDOW_SWITCH_OPEN_CHANNEL: usize = 2;
+pub const WINDOW_SWITCH_CLOSE_CHANNEL: usize = 3;
 
-pub struct InputPins {
-    adc_driver: AdcDriver<'static, ADC1>,
-    closing_current_sense_pin: AdcChannelDriver<'static, { attenuation::NONE }, Gpio2>,
-    opening_current_sense_pin: AdcChannelDriver<'static, { attenuation::NONE }, Gpio3>,
+/// Conversions per second over all channels, each one is sampled every 200us,
+/// fast enough for ripple frequencies up to 1kHz
+const SAMPLE_FREQUENCY: Hertz = Hertz(20_000);
+/// Conversions per frame, a frame is converted in 4ms
+const FRAME_MEASUREMENTS: usize = 80;
+/// Frames the driver keeps until they are read, older ones are dropped
+const FRAMES_COUNT: usize = 8;
+/// Longest wait for a frame before the stream counts as stalled
+const FRAME_TIMEOUT: Duration = Duration::from_millis(100);
+
+/// Converts the raw readings of a single channel into mV
+struct ChannelCalibration {
+    channel: adc_channel_t,
+    handle: adc_cali_handle_t,
 }
 
-impl InputPins {
-    /// Reads the voltage drop across the closing current sense resistor in mV
-    pub fn read_closing_voltage_drop(&mut self) -> Result<u16, EspError> {
-        let reading = self.adc_driver.read(&mut self.closing_current_sense_pin)?;
+// The handle is only used by the sampling thread owning the stream
+unsafe impl Send for ChannelCalibration {}
+
+impl ChannelCalibration {
+    fn new(channel: adc_channel_t, atten: adc_atten_t) -> Result<ChannelCalibration, EspError> {
+        let mut handle: adc_cali_handle_t = core::ptr::null_mut();
+
+        esp!(unsafe {
+            adc_cali_create_scheme_curve_fitting(
+                &adc_cali_curve_fitting_config_t {
+                    unit_id: adc_unit_t_ADC_UNIT_1,
+                    chan: channel,
+                    atten,
+                    bitwidth: adc_bitwidth_t_ADC_BITWIDTH_12,
+                },
+                &mut handle,
+            )
+        })?;
 
-        Ok(reading)
+        Ok(ChannelCalibration { channel, handle })
     }
 
-    /// Reads the voltage drop across the opening current sense resistor in mV
-    pub fn read_opening_voltage_drop(&mut self) -> Result<u16, EspError> {
-        let reading = self.adc_driver.read(&mut self.opening_current_sense_pin)?;
+    fn to_millivolts(&self, raw: u16) -> Result<u16, EspError> {
+        let mut millivolts = 0;
+        esp!(unsafe { adc_cali_raw_to_voltage(self.handle, raw as i32, &mut millivolts) })?;
 
-        Ok(reading)
+        Ok(millivolts.clamp(0, u16::MAX as i32) as u16)
     }
 }
 
-pub fn prepare_input_pins(adc: ADC1, closing_sense_pin: Gpio2, opening_sense_pin: Gpio3) -> Result<InputPins, EspError> {
-    let adc_driver = match AdcDriver::new(adc, &Config::new().calibration(true)) {
-        Ok(adc) => adc,
-        Err(e) => {
-            log::error!("Couldn't initialize ADC driver: {:?}", e);
-            panic!("Couldn't initialize ADC driver: {:?}", e);
+impl Drop for ChannelCalibration {
+    fn drop(&mut self) {
+        if let Err(err) = esp!(unsafe { adc_cali_delete_scheme_curve_fitting(self.handle) }) {
+            log::error!("Couldn't delete ADC calibration: {:?}", err);
         }
-    };
+    }
+}
 
-    let closing_current_sense_pin: AdcChannelDriver<{ attenuation::NONE }, Gpio2> = match AdcChannelDriver::new(closing_sense_pin) {
-        Ok(pin) => pin,
-        Err(e) => {
-            log::error!("Couldn't initialize current sense pin: {:?}", e);
-            panic!("Couldn't initialize current sense pin: {:?}", e);
-        }
-    };
+/// Current sense and window switch inputs converted continuously by the ADC, the samples arrive by DMA
+pub struct AnalogInputStream {
+    driver: AdcContDriver<'static>,
+    /// Ordered by input channel
+    calibrations: [ChannelCalibration; 4],
+    frame: [AdcMeasurement; FRAME_MEASUREMENTS],
+}
+
+impl AdcStream for AnalogInputStream {
+    type Error = EspError;
 
-    let opening_current_sense_pin: AdcChannelDriver<{ attenuation::NONE }, Gpio3> = match AdcChannelDriver::new(opening_sense_pin) {
-        Ok(pin) => pin,
-        Err(e) => {
-            log::error!("Couldn't initialize current sense pin: {:?}", e);
-            panic!("Couldn't initialize current sense pin: {:?}", e);
+    fn channel_count(&self) -> usize {
+        self.calibrations.len()
+    }
+
+    fn sample_interval_micros(&self) -> u32 {
+        self.calibrations.len() as u32 * 1_000_000 / SAMPLE_FREQUENCY.0
+    }
+
+    fn read_frame(&mut self, samples: &mut Vec<StreamSample>) -> Result<(), EspError> {
+        let count = self.driver.read(&mut self.frame, TickType::from(FRAME_TIMEOUT).0)?;
+
+        for measurement in &self.frame[..count] {
+            let channel = match self
+                .calibrations
+                .iter()
+                .position(|calibration| calibration.channel == measurement.channel())
+            {
+                Some(channel) => channel,
+                None => continue,
+            };
+
+            samples.push(StreamSample {
+                channel,
+                value: self.calibrations[channel].to_millivolts(measurement.data())?,
+            });
         }
-    };
 
-    Ok(InputPins {
-        adc_driver,
-        closing_current_sense_pin,
-        opening_current_sense_pin,
+        Ok(())
+    }
+}
+
+/// Starts converting both current sense inputs and the window switch ladder continuously.
+/// The switch ladder swings up to the supply, unlike the sense voltages it is read attenuated.
+pub fn prepare_analog_input_stream(
+    adc: ADC1,
+    closing_sense_pin: Gpio2,
+    opening_sense_pin: Gpio3,
+    switch_open_pin: Gpio4,
+    switch_close_pin: Gpio5,
+) -> Result<AnalogInputStream, EspError> {
+    let calibrations = [
+        ChannelCalibration::new(closing_sense_pin.adc_channel(), attenuation::NONE)?,
+        ChannelCalibration::new(opening_sense_pin.adc_channel(), attenuation::NONE)?,
+        ChannelCalibration::new(switch_open_pin.adc_channel(), attenuation::DB_11)?,
+        ChannelCalibration::new(switch_close_pin.adc_channel(), attenuation::DB_11)?,
+    ];
+
+    let mut driver = AdcContDriver::new(
+        adc,
+        &AdcContConfig::new()
+            .sample_freq(SAMPLE_FREQUENCY)
+            .frame_measurements(FRAME_MEASUREMENTS)
+            .frames_count(FRAMES_COUNT),
+        EmptyAdcChannels::chain(Attenuated::none(closing_sense_pin))
+            .chain(Attenuated::none(opening_sense_pin))
+            .chain(Attenuated::db11(switch_open_pin))
+            .chain(Attenuated::db11(switch_close_pin)),
+    )?;
+
+    driver.start()?;
+
+    Ok(AnalogInputStream {
+        driver,
+        calibrations,
+        frame: [AdcMeasurement::INIT; FRAME_MEASUREMENTS],
     })
 }
@@ -1,3 +1,23 @@
+#[cfg(feature = "esp")]
+mod clock;
+#[cfg(feature = "esp")]
 mod input;
+#[cfg(feature = "esp")]
 mod output;
+pub mod current_sensor;
+#[cfg(feature = "esp")]
+pub mod door_switch;
 pub mod power_window_driver;
+pub mod relay_interlock;
+pub mod reset;
+pub mod sampled_input;
+pub mod sim;
+#[cfg(feature = "esp")]
+pub mod storage;
+pub mod traits;
+#[cfg(feature = "esp")]
+pub mod watchdog;
+
+#[cfg(feature = "esp")]
+pub type DefaultPowerWindowDriver =
+    power_window_driver::PowerWindowDriver<output::OutputPins, sampled_input::SampledCurrentSense, clock::SystemClock>;
@@ -1,26 +1,28 @@
 use esp_idf_hal::gpio::{Gpio10, Gpio11, Output, PinDriver};
 use esp_idf_sys::EspError;
 
+use super::traits::{HalError, RelayOutputs};
+
 pub struct OutputPins {
     closing_pin: PinDriver<'static, Gpio10, Output>,
     opening_pin: PinDriver<'static, Gpio11, Output>,
 }
 
-impl OutputPins {
-    pub fn set_open_high(&mut self) -> Result<(), EspError> {
-        self.opening_pin.set_high()
+impl RelayOutputs for OutputPins {
+    fn set_open_high(&mut self) -> Result<(), HalError> {
+        Ok(self.opening_pin.set_high()?)
     }
 
-    pub fn set_open_low(&mut self) -> Result<(), EspError> {
-        self.opening_pin.set_low()
+    fn set_open_low(&mut self) -> Result<(), HalError> {
+        Ok(self.opening_pin.set_low()?)
     }
 
-    pub fn set_close_high(&mut self) -> Result<(), EspError> {
-        self.closing_pin.set_high()
+    fn set_close_high(&mut self) -> Result<(), HalError> {
+        Ok(self.closing_pin.set_high()?)
     }
 
-    pub fn set_close_low(&mut self) -> Result<(), EspError> {
-        self.closing_pin.set_low()
+    fn set_close_low(&mut self) -> Result<(), HalError> {
+        Ok(self.closing_pin.set_low()?)
     }
 }
 
@@ -1,14 +1,57 @@
+use std::sync::Arc;
+
+#[cfg(feature = "esp")]
 use esp_idf_hal::{
     adc::ADC1,
-    gpio::{Gpio10, Gpio11, Gpio2, Gpio3}, delay::FreeRtos,
+    gpio::{Gpio10, Gpio11, Gpio2, Gpio3, Gpio4, Gpio5},
 };
+#[cfg(feature = "esp")]
 use esp_idf_sys::EspError;
+#[cfg(feature = "esp")]
+use shared_lib::sampling::{esp_clock::EspSamplingClock, filter::FilterConfig, stream::StreamSamplingService};
+use tokio::sync::Notify;
+
+#[cfg(feature = "esp")]
+use super::{
+    clock::SystemClock,
+    input::{
+        prepare_analog_input_stream, CLOSING_CURRENT_SENSE_CHANNEL, OPENING_CURRENT_SENSE_CHANNEL,
+        WINDOW_SWITCH_CLOSE_CHANNEL, WINDOW_SWITCH_OPEN_CHANNEL,
+    },
+    output::prepare_output_pins,
+    sampled_input::{SampledCurrentSense, SampledWindowSwitch},
+    DefaultPowerWindowDriver,
+};
+use shared_lib::{
+    dto::pw_config::{DEFAULT_SENSE_GAIN, DEFAULT_SENSE_SATURATION, DEFAULT_SENSE_ZERO_SETTLE_TIME},
+    units::{Milliamps, Milliseconds, Millivolts},
+};
 
 use super::{
-    input::{prepare_input_pins, InputPins},
-    output::{prepare_output_pins, OutputPins},
+    current_sensor::{CurrentSensor, CurrentSensorConfig},
+    relay_interlock::RelayInterlock,
+    traits::{Clock, CurrentSenseInputs, HalError, RelayOutputs},
 };
 
+/// Time the motor current needs to decay after a relay has been switched low
+const RELAY_RELEASE_TIME: Milliseconds = Milliseconds(150);
+/// Current which may flow through a sense resistor whose relay is low
+const RELAY_OFF_CURRENT_LIMIT: Milliamps = Milliamps(500);
+/// Filter of the current readings the interrupts act on, removing spikes and smoothing over about 4ms
+#[cfg(feature = "esp")]
+const CURRENT_FILTER: FilterConfig = FilterConfig {
+    median_window: 5,
+    iir_alpha: 0.05,
+};
+/// Filter of the window switch ladder, removing contact bounce over about 20ms
+#[cfg(feature = "esp")]
+const WINDOW_SWITCH_FILTER: FilterConfig = FilterConfig {
+    median_window: 15,
+    iir_alpha: 0.01,
+};
+#[cfg(feature = "esp")]
+const SAMPLING_STACK_SIZE: usize = 4096;
+
 pub enum WindowDriverState {
     INTERRUPTED = 0,
 
@@ -16,95 +59,328 @@ pub enum WindowDriverState {
     CLOSING = 0b10,
 }
 
+/// Driver faults which block any further motion until they are cleared
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DriverFault {
+    /// Current flows through a relay which has been switched low
+    RelayWelded = 1,
+    /// A relay couldn't be switched, its state is unknown
+    RelaySwitchFailed = 2,
+}
+
+#[cfg(feature = "esp")]
 pub struct PowerWindowDriverPins {
     pub adc: ADC1,
     pub window_closing_sense_pin: Gpio2,
     pub window_opening_sense_pin: Gpio3,
+    pub window_switch_open_pin: Gpio4,
+    pub window_switch_close_pin: Gpio5,
     pub window_closing_pin: Gpio10,
     pub window_opening_pin: Gpio11,
 }
 
-pub struct PowerWindowDriver {
-    input: InputPins,
-    output: OutputPins,
+const DEFAULT_CURRENT_SENSOR: CurrentSensorConfig = CurrentSensorConfig {
+    gain: DEFAULT_SENSE_GAIN,
+    saturation: DEFAULT_SENSE_SATURATION,
+};
+
+pub struct PowerWindowDriver<TOutput, TInput, TClock>
+where
+    TOutput: RelayOutputs,
+    TInput: CurrentSenseInputs,
+    TClock: Clock,
+{
+    input: TInput,
+    output: RelayInterlock<TOutput>,
+    clock: TClock,
+
+    closing_sensor: CurrentSensor,
+    opening_sensor: CurrentSensor,
+    /// How long both relays have to be off before the sensors are zeroed
+    zero_settle_time: Milliseconds,
+    /// Voltage drops drained by the control loop, kept to avoid allocating on every tick
+    sample_scratch: Vec<Millivolts>,
 
     pub state: WindowDriverState,
+    last_switch_millis: u128,
+    fault: Option<DriverFault>,
 }
 
 pub struct WindowCurrentState {
-    pub closing_current: u16,
-    pub opening_current: u16,
+    pub closing: Milliamps,
+    pub opening: Milliamps,
+    /// Either sense voltage was at the end of the ADC range
+    pub saturated: bool,
 }
 
-impl PowerWindowDriver {
-    pub fn new(pins: PowerWindowDriverPins) -> Result<PowerWindowDriver, EspError> {
-        Ok(PowerWindowDriver {
-            input: prepare_input_pins(
-                pins.adc,
-                pins.window_closing_sense_pin,
-                pins.window_opening_sense_pin,
-            )?,
-            output: prepare_output_pins(pins.window_closing_pin, pins.window_opening_pin)?,
+impl<TOutput, TInput, TClock> PowerWindowDriver<TOutput, TInput, TClock>
+where
+    TOutput: RelayOutputs,
+    TInput: CurrentSenseInputs,
+    TClock: Clock,
+{
+    /// Creates the driver and switches both relays low, so their state is known from the start.
+    /// A relay which can't be switched low latches a fault right away.
+    pub fn new(output: TOutput, input: TInput, clock: TClock) -> PowerWindowDriver<TOutput, TInput, TClock> {
+        let mut driver = PowerWindowDriver {
+            input,
+            output: RelayInterlock::new(output),
+            clock,
+            closing_sensor: CurrentSensor::new(DEFAULT_CURRENT_SENSOR),
+            opening_sensor: CurrentSensor::new(DEFAULT_CURRENT_SENSOR),
+            zero_settle_time: DEFAULT_SENSE_ZERO_SETTLE_TIME,
+            sample_scratch: Vec::new(),
             state: WindowDriverState::INTERRUPTED,
-        })
+            last_switch_millis: 0,
+            fault: None,
+        };
+
+        if let Err(err) = driver.interrupt() {
+            log::error!("Couldn't switch relays low at startup: {:?}", err);
+        }
+
+        driver
+    }
+
+    /// Milliseconds since boot, as seen by the driver's clock
+    pub fn now_millis(&self) -> u128 {
+        self.clock.now_millis()
     }
 
-    /// Reads the current in Amps
-    pub fn read_current(&mut self) -> Result<WindowCurrentState, EspError> {
-        let closing_millivolts = self.input.read_closing_voltage_drop()?;
-        let opening_millivolts = self.input.read_opening_voltage_drop()?;
+    pub fn configure_current_sense(
+        &mut self,
+        closing: CurrentSensorConfig,
+        opening: CurrentSensorConfig,
+        zero_settle_time: Milliseconds,
+    ) {
+        self.closing_sensor.set_config(closing);
+        self.opening_sensor.set_config(opening);
+        self.zero_settle_time = zero_settle_time;
+    }
 
-        // Should be 80mV/A
-        let closing_current = closing_millivolts * 80;
-        let opening_current = opening_millivolts * 80;
+    /// Learned zero current offsets of the closing and opening sensors
+    pub fn current_sense_offsets(&self) -> (Millivolts, Millivolts) {
+        (self.closing_sensor.offset(), self.opening_sensor.offset())
+    }
+
+    /// Reads the filtered motor currents, zeroing the sensors first if no current can flow
+    pub fn read_current(&mut self) -> Result<WindowCurrentState, HalError> {
+        let closing_voltage = self.input.read_closing_voltage_drop()?;
+        let opening_voltage = self.input.read_opening_voltage_drop()?;
+
+        if self.is_current_off_settled() {
+            self.closing_sensor.zero(closing_voltage);
+            self.opening_sensor.zero(opening_voltage);
+        }
+
+        let closing = self.closing_sensor.to_current(closing_voltage);
+        let opening = self.opening_sensor.to_current(opening_voltage);
 
         Ok(WindowCurrentState {
-            closing_current,
-            opening_current
+            closing: closing.current,
+            opening: opening.current,
+            saturated: closing.saturated || opening.saturated,
         })
     }
 
-    pub fn start_opening(&mut self) -> Result<(), EspError> {
+    /// Appends the unfiltered currents of the energised relay sampled since the previous call.
+    /// The other channel is skipped, nothing is appended while the motor is stopped.
+    pub fn drain_current_samples(&mut self, samples: &mut Vec<Milliamps>) -> Result<(), HalError> {
+        self.sample_scratch.clear();
+
+        let sensor = match self.state {
+            WindowDriverState::OPENING => {
+                self.input.skip_closing_voltage_drops();
+                self.input.drain_opening_voltage_drops(&mut self.sample_scratch)?;
+                &self.opening_sensor
+            }
+            WindowDriverState::CLOSING => {
+                self.input.skip_opening_voltage_drops();
+                self.input.drain_closing_voltage_drops(&mut self.sample_scratch)?;
+                &self.closing_sensor
+            }
+            WindowDriverState::INTERRUPTED => {
+                self.input.skip_closing_voltage_drops();
+                self.input.skip_opening_voltage_drops();
+                return Ok(());
+            }
+        };
+
+        samples.extend(self.sample_scratch.iter().map(|voltage| sensor.to_current(*voltage).current));
+
+        Ok(())
+    }
+
+    /// Wakes the current trip as soon as the sampled current of the energised relay exceeds `limit`,
+    /// `None` disarms it. A trip stays disarmed until it is armed again.
+    pub fn arm_current_trip(&mut self, limit: Option<Milliamps>) {
+        let (closing, opening) = match (&self.state, limit) {
+            (WindowDriverState::CLOSING, Some(limit)) => (Some(self.closing_sensor.to_voltage(limit)), None),
+            (WindowDriverState::OPENING, Some(limit)) => (None, Some(self.opening_sensor.to_voltage(limit))),
+            _ => (None, None),
+        };
+
+        self.input.arm_trip(closing, opening);
+    }
+
+    /// Notified whenever the armed current trip has been exceeded
+    pub fn current_trip(&self) -> Arc<Notify> {
+        self.input.trip_signal()
+    }
+
+    /// Latched fault, motion is refused while there is one
+    pub fn fault(&self) -> Option<DriverFault> {
+        self.fault
+    }
+
+    pub fn clear_fault(&mut self) {
+        self.fault = None;
+    }
+
+    /// Compares the measured current against the commanded relay state,
+    /// returns a fault which has been latched by this check
+    pub fn supervise(&mut self, current_state: &WindowCurrentState) -> Option<DriverFault> {
+        if self.fault.is_some() {
+            return None;
+        }
+
+        if self.clock.now_millis().saturating_sub(self.last_switch_millis) < RELAY_RELEASE_TIME.as_millis() {
+            return None;
+        }
+
+        let opening_leaks = !self.output.is_open_commanded()
+            && current_state.opening > RELAY_OFF_CURRENT_LIMIT;
+        let closing_leaks = !self.output.is_close_commanded()
+            && current_state.closing > RELAY_OFF_CURRENT_LIMIT;
+
+        if !opening_leaks && !closing_leaks {
+            return None;
+        }
+
+        log::error!(
+            "Current through a released relay: closing_{}, opening_{}",
+            current_state.closing,
+            current_state.opening
+        );
+
+        self.fault = Some(DriverFault::RelayWelded);
+        self.fault
+    }
+
+    pub fn start_opening(&mut self) -> Result<(), HalError> {
         if matches!(self.state, WindowDriverState::OPENING) {
             return Ok(());
         }
 
+        if self.fault.is_some() {
+            return Err(HalError::DriverFault);
+        }
+
         log::info!("Setting relays to opening mode...");
 
-        self.output.set_close_low()?;
-        FreeRtos::delay_ms(50);
-        self.output.set_open_high()?;
+        self.switch(|output| output.set_close_low())?;
+        self.clock.delay_ms(50);
+        self.switch(|output| output.set_open_high())?;
 
         self.state = WindowDriverState::OPENING;
 
         Ok(())
     }
 
-    pub fn start_closing(&mut self) -> Result<(), EspError> {
+    pub fn start_closing(&mut self) -> Result<(), HalError> {
         if matches!(self.state, WindowDriverState::CLOSING) {
             return Ok(());
         }
 
+        if self.fault.is_some() {
+            return Err(HalError::DriverFault);
+        }
+
         log::info!("Setting relays to closing mode...");
 
-        self.output.set_open_low()?;
-        FreeRtos::delay_ms(50);
-        self.output.set_close_high()?;
+        self.switch(|output| output.set_open_low())?;
+        self.clock.delay_ms(50);
+        self.switch(|output| output.set_close_high())?;
 
         self.state = WindowDriverState::CLOSING;
 
         Ok(())
     }
 
-
-    pub fn interrupt(&mut self) -> Result<(), EspError> {
+    pub fn interrupt(&mut self) -> Result<(), HalError> {
         log::info!("Setting relays into stopped mode...");
 
-        self.output.set_close_low()?;
-        self.output.set_open_low()?;
-
         self.state = WindowDriverState::INTERRUPTED;
+        self.switch(|output| output.all_low())
+    }
 
-        Ok(())
+    /// Whether both relays have been confirmed off for long enough that no motor current flows
+    fn is_current_off_settled(&self) -> bool {
+        matches!(self.state, WindowDriverState::INTERRUPTED)
+            && self.fault.is_none()
+            && !self.output.is_open_commanded()
+            && !self.output.is_close_commanded()
+            && self.clock.now_millis().saturating_sub(self.last_switch_millis) >= self.zero_settle_time.as_millis()
+    }
+
+    /// Applies a relay switch, latching a fault and stopping if it fails
+    fn switch(
+        &mut self,
+        switch: impl FnOnce(&mut RelayInterlock<TOutput>) -> Result<(), HalError>,
+    ) -> Result<(), HalError> {
+        self.last_switch_millis = self.clock.now_millis();
+
+        let result = switch(&mut self.output);
+
+        if result.is_err() {
+            // The interlock has already tried to switch both relays low
+            self.state = WindowDriverState::INTERRUPTED;
+            self.fault = Some(DriverFault::RelaySwitchFailed);
+        }
+
+        result
     }
 }
+
+/// Prepares the driver and starts sampling its current sense inputs on a separate thread.
+/// The window switch on the door shares the ADC, it is sampled along and returned with the driver.
+#[cfg(feature = "esp")]
+pub fn prepare_power_window_driver(
+    pins: PowerWindowDriverPins,
+) -> Result<(DefaultPowerWindowDriver, SampledWindowSwitch), EspError> {
+    let analog_input_stream = prepare_analog_input_stream(
+        pins.adc,
+        pins.window_closing_sense_pin,
+        pins.window_opening_sense_pin,
+        pins.window_switch_open_pin,
+        pins.window_switch_close_pin,
+    )?;
+
+    let sampling = StreamSamplingService::new(
+        analog_input_stream,
+        EspSamplingClock,
+        &[CURRENT_FILTER, CURRENT_FILTER, WINDOW_SWITCH_FILTER, WINDOW_SWITCH_FILTER],
+    );
+
+    let current_sense = SampledCurrentSense::new(
+        sampling.channel(CLOSING_CURRENT_SENSE_CHANNEL),
+        sampling.channel(OPENING_CURRENT_SENSE_CHANNEL),
+    );
+    let window_switch = SampledWindowSwitch::new(
+        sampling.channel(WINDOW_SWITCH_OPEN_CHANNEL),
+        sampling.channel(WINDOW_SWITCH_CLOSE_CHANNEL),
+    );
+
+    if let Err(err) = sampling.spawn(SAMPLING_STACK_SIZE) {
+        log::error!("Couldn't start current sampling: {:?}", err);
+        panic!("Couldn't start current sampling: {:?}", err);
+    }
+
+    let driver = PowerWindowDriver::new(
+        prepare_output_pins(pins.window_closing_pin, pins.window_opening_pin)?,
+        current_sense,
+        SystemClock,
+    );
+
+    Ok((driver, window_switch))
+}
@@ -0,0 +1,113 @@
+use super::traits::{HalError, RelayOutputs};
+
+/// Last state a relay has been successfully switched to
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum RelayCommand {
+    Low,
+    High,
+    /// Never switched or the last switch failed, the relay may be in either state
+    Unknown,
+}
+
+/// Wraps the relay outputs so that one relay can only be energised once the
+/// other one has been confirmed low.
+///
+/// A failed switch leaves the relay in an unknown state, which blocks the
+/// other relay until both have been switched low successfully again.
+pub struct RelayInterlock<TOutput>
+where
+    TOutput: RelayOutputs,
+{
+    output: TOutput,
+    open: RelayCommand,
+    close: RelayCommand,
+}
+
+impl<TOutput> RelayInterlock<TOutput>
+where
+    TOutput: RelayOutputs,
+{
+    pub fn new(output: TOutput) -> RelayInterlock<TOutput> {
+        RelayInterlock {
+            output,
+            open: RelayCommand::Unknown,
+            close: RelayCommand::Unknown,
+        }
+    }
+
+    /// Whether the opening relay may be energised
+    pub fn is_open_commanded(&self) -> bool {
+        self.open != RelayCommand::Low
+    }
+
+    /// Whether the closing relay may be energised
+    pub fn is_close_commanded(&self) -> bool {
+        self.close != RelayCommand::Low
+    }
+
+    pub fn set_open_high(&mut self) -> Result<(), HalError> {
+        if self.close != RelayCommand::Low {
+            return Err(HalError::Interlock("Closing relay isn't confirmed low"));
+        }
+
+        let result = self.output.set_open_high();
+        self.open = self.settle(result.is_ok(), RelayCommand::High);
+
+        self.all_low_on_error(result)
+    }
+
+    pub fn set_close_high(&mut self) -> Result<(), HalError> {
+        if self.open != RelayCommand::Low {
+            return Err(HalError::Interlock("Opening relay isn't confirmed low"));
+        }
+
+        let result = self.output.set_close_high();
+        self.close = self.settle(result.is_ok(), RelayCommand::High);
+
+        self.all_low_on_error(result)
+    }
+
+    pub fn set_open_low(&mut self) -> Result<(), HalError> {
+        let result = self.output.set_open_low();
+        self.open = self.settle(result.is_ok(), RelayCommand::Low);
+
+        self.all_low_on_error(result)
+    }
+
+    pub fn set_close_low(&mut self) -> Result<(), HalError> {
+        let result = self.output.set_close_low();
+        self.close = self.settle(result.is_ok(), RelayCommand::Low);
+
+        self.all_low_on_error(result)
+    }
+
+    /// Switches both relays low, trying the second one even if the first one fails
+    pub fn all_low(&mut self) -> Result<(), HalError> {
+        let open_result = self.output.set_open_low();
+        self.open = self.settle(open_result.is_ok(), RelayCommand::Low);
+
+        let close_result = self.output.set_close_low();
+        self.close = self.settle(close_result.is_ok(), RelayCommand::Low);
+
+        open_result.and(close_result)
+    }
+
+    fn settle(&self, switched: bool, command: RelayCommand) -> RelayCommand {
+        match switched {
+            true => command,
+            false => RelayCommand::Unknown,
+        }
+    }
+
+    fn all_low_on_error(&mut self, result: Result<(), HalError>) -> Result<(), HalError> {
+        if result.is_err() {
+            log::error!("Relay switch failed, switching both relays low...");
+
+            if let Err(err) = self.all_low() {
+                log::error!("Couldn't switch relays low: {:?}", err);
+            }
+        }
+
+        result
+    }
+}
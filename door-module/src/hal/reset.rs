@@ -0,0 +1,66 @@
+/// Marks a record written by this firmware, anything else in no-init memory is garbage after a power-on
+const RECORD_MAGIC: u16 = 0xD0A5;
+
+/// Why the system started, as far as it matters to the power windows
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ResetReason {
+    PowerOn = 1,
+    /// Restarted on purpose, e.g. after an update
+    Software = 2,
+    Panic = 3,
+    /// Reset by a watchdog after a hang
+    Watchdog = 4,
+    /// The supply dropped too low, e.g. while the motor started
+    Brownout = 5,
+    Other = 6,
+}
+
+impl ResetReason {
+    /// Whether the reset wasn't asked for, interrupting whatever the window was doing
+    pub fn is_unexpected(&self) -> bool {
+        matches!(self, ResetReason::Panic | ResetReason::Watchdog | ResetReason::Brownout)
+    }
+}
+
+/// What the control loop last saw before a reset
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ResetRecord {
+    /// Raw value of the window state
+    pub state: u8,
+    /// Whether a relay was energised
+    pub motor_running: bool,
+}
+
+impl ResetRecord {
+    /// Packs the record into a single word, so it can be written to memory surviving a reset at once
+    pub fn to_word(&self) -> u32 {
+        (RECORD_MAGIC as u32) << 16 | (self.motor_running as u32) << 8 | self.state as u32
+    }
+
+    /// Unpacks a record written by `to_word`, returns `None` for anything else
+    pub fn from_word(word: u32) -> Option<ResetRecord> {
+        if (word >> 16) as u16 != RECORD_MAGIC {
+            return None;
+        }
+
+        Some(ResetRecord {
+            state: word as u8,
+            motor_running: (word >> 8) as u8 & 1 == 1,
+        })
+    }
+}
+
+/// Reset the system has started from
+#[derive(Debug, Clone, Copy)]
+pub struct ResetReport {
+    pub reason: ResetReason,
+    /// Last record of the previous run, there is none after a power-on
+    pub record: Option<ResetRecord>,
+}
+
+impl ResetReport {
+    /// Whether the window was moving when the system went down unexpectedly
+    pub fn is_unexpected_during_motion(&self) -> bool {
+        self.reason.is_unexpected() && self.record.is_some_and(|record| record.motor_running)
+    }
+}
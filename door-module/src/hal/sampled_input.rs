@@ -0,0 +1,107 @@
+use std::sync::Arc;
+
+use shared_lib::{
+    sampling::service::{ChannelHandle, Sample},
+    units::Millivolts,
+    window_switch::{get_state_for_voltages, PowerWindowButtonState},
+};
+use tokio::sync::Notify;
+
+use super::traits::{CurrentSenseInputs, HalError, WindowSwitchInput};
+
+/// Current sense inputs fed by the ADC sampling service, the ADC is calibrated to read mV.
+/// The trip levels are compared against the filtered readings.
+pub struct SampledCurrentSense {
+    closing: ChannelHandle,
+    opening: ChannelHandle,
+    /// Timestamps of the last drained samples
+    closing_drained_micros: u64,
+    opening_drained_micros: u64,
+    scratch: Vec<Sample>,
+}
+
+impl SampledCurrentSense {
+    pub fn new(closing: ChannelHandle, opening: ChannelHandle) -> SampledCurrentSense {
+        SampledCurrentSense {
+            closing,
+            opening,
+            closing_drained_micros: 0,
+            opening_drained_micros: 0,
+            scratch: Vec::new(),
+        }
+    }
+}
+
+fn drain(
+    channel: &ChannelHandle,
+    drained_micros: &mut u64,
+    scratch: &mut Vec<Sample>,
+    samples: &mut Vec<Millivolts>,
+) -> Result<(), HalError> {
+    scratch.clear();
+    channel.samples_since(*drained_micros, scratch);
+
+    if let Some(latest) = scratch.last() {
+        *drained_micros = latest.timestamp_micros;
+    }
+    samples.extend(scratch.iter().map(|sample| Millivolts(sample.value)));
+
+    Ok(())
+}
+
+impl CurrentSenseInputs for SampledCurrentSense {
+    fn read_closing_voltage_drop(&mut self) -> Result<Millivolts, HalError> {
+        Ok(Millivolts(self.closing.latest().filtered))
+    }
+
+    fn read_opening_voltage_drop(&mut self) -> Result<Millivolts, HalError> {
+        Ok(Millivolts(self.opening.latest().filtered))
+    }
+
+    fn drain_closing_voltage_drops(&mut self, samples: &mut Vec<Millivolts>) -> Result<(), HalError> {
+        drain(&self.closing, &mut self.closing_drained_micros, &mut self.scratch, samples)
+    }
+
+    fn drain_opening_voltage_drops(&mut self, samples: &mut Vec<Millivolts>) -> Result<(), HalError> {
+        drain(&self.opening, &mut self.opening_drained_micros, &mut self.scratch, samples)
+    }
+
+    fn skip_closing_voltage_drops(&mut self) {
+        self.closing_drained_micros = self.closing.latest().raw.timestamp_micros;
+    }
+
+    fn skip_opening_voltage_drops(&mut self) {
+        self.opening_drained_micros = self.opening.latest().raw.timestamp_micros;
+    }
+
+    fn arm_trip(&mut self, closing: Option<Millivolts>, opening: Option<Millivolts>) {
+        self.closing.arm_trip(closing.map(|level| level.0));
+        self.opening.arm_trip(opening.map(|level| level.0));
+    }
+
+    fn trip_signal(&self) -> Arc<Notify> {
+        // Both channels are sampled by the same service and share its signal
+        self.closing.trip_signal()
+    }
+}
+
+/// Window switch ladder fed by the ADC sampling service, classified like the master switch
+pub struct SampledWindowSwitch {
+    open: ChannelHandle,
+    close: ChannelHandle,
+}
+
+impl SampledWindowSwitch {
+    pub fn new(open: ChannelHandle, close: ChannelHandle) -> SampledWindowSwitch {
+        SampledWindowSwitch { open, close }
+    }
+}
+
+impl WindowSwitchInput for SampledWindowSwitch {
+    fn read_switch_state(&mut self) -> Result<PowerWindowButtonState, HalError> {
+        Ok(get_state_for_voltages(
+            Millivolts(self.open.latest().filtered),
+            Millivolts(self.close.latest().filtered),
+        ))
+    }
+}
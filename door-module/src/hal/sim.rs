@@ -0,0 +1,455 @@
+use std::{
+    collections::{HashMap, VecDeque},
+    sync::{Arc, Mutex, MutexGuard},
+};
+
+use shared_lib::{
+    units::{Milliamps, Milliseconds, Millivolts, MillivoltsPerAmp},
+    window_switch::{get_state_for_voltages, PowerWindowButtonState},
+};
+use tokio::sync::Notify;
+
+use super::traits::{
+    Clock, CurrentSenseInputs, DoorSwitchInput, HalError, PersistentStore, RelayOutputs, WindowSwitchInput,
+};
+
+/// Gain of the simulated current sense amplifiers, the same hardware as on the door module.
+/// Kept apart from the configured gain, so a wrong config shows as wrong currents.
+const SIMULATED_SENSE_GAIN: MillivoltsPerAmp = MillivoltsPerAmp(80);
+
+/// Physical properties of the simulated window and its motor
+#[derive(Debug, Clone, Copy)]
+pub struct SimulatedWindowConfig {
+    /// Time the glass needs to travel from fully closed to fully open
+    pub opening_travel_time: Milliseconds,
+    /// Time the glass needs to travel from fully open to fully closed
+    pub closing_travel_time: Milliseconds,
+    /// Current drawn while the glass travels freely
+    pub running_current: Milliamps,
+    /// Current drawn while the motor is stalled against an end stop
+    pub stall_current: Milliamps,
+    /// Current drawn while the glass presses against an obstruction
+    pub obstruction_current: Milliamps,
+    /// Gain of the current sense amplifier
+    pub sense_gain: MillivoltsPerAmp,
+    /// Output of the current sense amplifier without any current
+    pub sense_offset: Millivolts,
+    /// Commutator ripples the motor produces over the full travel
+    pub ripples_per_travel: u32,
+    /// Amplitude of the commutator ripple on top of the running current
+    pub ripple_amplitude: Milliamps,
+    /// Interval the current sense inputs are sampled at while time passes
+    pub sample_interval_micros: u32,
+}
+
+impl Default for SimulatedWindowConfig {
+    fn default() -> Self {
+        SimulatedWindowConfig {
+            opening_travel_time: Milliseconds(3500),
+            closing_travel_time: Milliseconds(4000),
+            running_current: Milliamps(3000),
+            stall_current: Milliamps(12000),
+            obstruction_current: Milliamps(7000),
+            sense_gain: SIMULATED_SENSE_GAIN,
+            sense_offset: Millivolts(0),
+            ripples_per_travel: 2000,
+            ripple_amplitude: Milliamps(0),
+            sample_interval_micros: 200,
+        }
+    }
+}
+
+/// Samples kept per channel until they are drained, older ones are dropped
+const SAMPLE_HISTORY_SIZE: usize = 512;
+
+struct SimulatedWindowState {
+    config: SimulatedWindowConfig,
+
+    now_micros: u128,
+    /// 0.0 is fully closed, 1.0 is fully open
+    position: f32,
+    /// Position at which closing glass gets stuck, if any
+    obstruction: Option<f32>,
+    door_open: bool,
+    /// Ladder voltages of the window switch on the door, opening first
+    window_switch_voltages: (Millivolts, Millivolts),
+
+    opening_relay: bool,
+    closing_relay: bool,
+    relay_overlap_detected: bool,
+
+    /// Welded relays stay energised whatever they are switched to
+    opening_relay_welded: bool,
+    closing_relay_welded: bool,
+
+    /// Time passed since the last sample was taken
+    unsampled_micros: u32,
+    closing_samples: VecDeque<Millivolts>,
+    opening_samples: VecDeque<Millivolts>,
+    closing_trip: Option<Millivolts>,
+    opening_trip: Option<Millivolts>,
+    trip: Arc<Notify>,
+}
+
+impl SimulatedWindowState {
+    /// Moves time forward in steps of the sample interval, sampling the current sense inputs after each one
+    fn advance_micros(&mut self, micros: u32) {
+        let interval_micros = self.config.sample_interval_micros.max(1);
+        let mut remaining_micros = micros;
+
+        while remaining_micros > 0 {
+            let step_micros = remaining_micros.min(interval_micros - self.unsampled_micros);
+            self.move_glass(step_micros);
+            remaining_micros -= step_micros;
+
+            self.unsampled_micros += step_micros;
+            if self.unsampled_micros >= interval_micros {
+                self.unsampled_micros = 0;
+                self.take_samples();
+            }
+        }
+    }
+
+    fn take_samples(&mut self) {
+        let closing = self.sense_voltage(self.closing_current());
+        let opening = self.sense_voltage(self.opening_current());
+
+        for (samples, sample) in [(&mut self.closing_samples, closing), (&mut self.opening_samples, opening)] {
+            if samples.len() == SAMPLE_HISTORY_SIZE {
+                samples.pop_front();
+            }
+            samples.push_back(sample);
+        }
+
+        for (level, sample) in [(&mut self.closing_trip, closing), (&mut self.opening_trip, opening)] {
+            if level.is_some_and(|level| sample > level) {
+                *level = None;
+                self.trip.notify_one();
+            }
+        }
+    }
+
+    fn move_glass(&mut self, micros: u32) {
+        let millis = micros as f32 / 1000.0;
+
+        match (self.opening_relay, self.closing_relay) {
+            (true, false) => {
+                let travel = millis / self.config.opening_travel_time.0 as f32;
+                self.position = (self.position + travel).min(1.0);
+            }
+            (false, true) => {
+                let travel = millis / self.config.closing_travel_time.0 as f32;
+                let limit = self.obstruction.unwrap_or(0.0).max(0.0);
+
+                if self.position > limit {
+                    self.position = (self.position - travel).max(limit);
+                }
+            }
+            (true, true) => self.relay_overlap_detected = true,
+            (false, false) => {}
+        }
+
+        self.now_micros += micros as u128;
+    }
+
+    /// Running current including the commutator ripple, which follows the motor and therefore the glass position
+    fn running_current(&self) -> Milliamps {
+        let angle = 2.0 * std::f32::consts::PI * self.position * self.config.ripples_per_travel as f32;
+        let ripple = self.config.ripple_amplitude.0 as f32 * angle.sin();
+
+        Milliamps((self.config.running_current.0 as f32 + ripple).clamp(0.0, u16::MAX as f32) as u16)
+    }
+
+    fn opening_current(&self) -> Milliamps {
+        if !self.opening_relay || self.closing_relay {
+            return Milliamps::ZERO;
+        }
+
+        if self.position >= 1.0 {
+            self.config.stall_current
+        } else {
+            self.running_current()
+        }
+    }
+
+    fn closing_current(&self) -> Milliamps {
+        if !self.closing_relay || self.opening_relay {
+            return Milliamps::ZERO;
+        }
+
+        if self.position <= 0.0 {
+            return self.config.stall_current;
+        }
+
+        match self.obstruction {
+            Some(obstruction) if self.position <= obstruction => self.config.obstruction_current,
+            _ => self.running_current(),
+        }
+    }
+
+    fn sense_voltage(&self, current: Milliamps) -> Millivolts {
+        let voltage = self.config.sense_gain.checked_voltage(current).unwrap_or(Millivolts::MAX);
+
+        voltage.saturating_add(self.config.sense_offset)
+    }
+}
+
+/// Simulated window motor, relays and current sensing, allowing the
+/// power window service to run without any hardware attached.
+///
+/// Time only moves forward when `advance` is called, or when the driver
+/// delays on the simulated clock. The current sense inputs are sampled at
+/// a fixed interval meanwhile, like the sampling service would.
+#[derive(Clone)]
+pub struct SimulatedWindow {
+    state: Arc<Mutex<SimulatedWindowState>>,
+}
+
+impl SimulatedWindow {
+    pub fn new(config: SimulatedWindowConfig, initial_position: f32) -> SimulatedWindow {
+        SimulatedWindow {
+            state: Arc::new(Mutex::new(SimulatedWindowState {
+                config,
+                now_micros: 0,
+                position: initial_position.clamp(0.0, 1.0),
+                obstruction: None,
+                door_open: false,
+                window_switch_voltages: (Millivolts(0), Millivolts(0)),
+                opening_relay: false,
+                closing_relay: false,
+                relay_overlap_detected: false,
+                opening_relay_welded: false,
+                closing_relay_welded: false,
+                unsampled_micros: 0,
+                closing_samples: VecDeque::new(),
+                opening_samples: VecDeque::new(),
+                closing_trip: None,
+                opening_trip: None,
+                trip: Arc::new(Notify::new()),
+            })),
+        }
+    }
+
+    pub fn relays(&self) -> SimulatedRelays {
+        SimulatedRelays { window: self.clone() }
+    }
+
+    pub fn current_sense(&self) -> SimulatedCurrentSense {
+        SimulatedCurrentSense { window: self.clone() }
+    }
+
+    pub fn clock(&self) -> SimulatedClock {
+        SimulatedClock { window: self.clone() }
+    }
+
+    pub fn door_switch(&self) -> SimulatedDoorSwitch {
+        SimulatedDoorSwitch { window: self.clone() }
+    }
+
+    pub fn window_switch(&self) -> SimulatedWindowSwitch {
+        SimulatedWindowSwitch { window: self.clone() }
+    }
+
+    /// Lets the given amount of time pass, moving the glass if the motor runs
+    pub fn advance(&self, millis: u32) {
+        self.lock().advance_micros(millis * 1000);
+    }
+
+    /// Current glass position, 0.0 is fully closed, 1.0 is fully open
+    pub fn position(&self) -> f32 {
+        self.lock().position
+    }
+
+    /// Places an obstruction at the given position, closing glass stops there
+    pub fn set_obstruction(&self, obstruction: Option<f32>) {
+        self.lock().obstruction = obstruction;
+    }
+
+    pub fn set_door_open(&self, door_open: bool) {
+        self.lock().door_open = door_open;
+    }
+
+    /// Sets the ladder voltages of the window switch on the door
+    pub fn set_window_switch(&self, open: Millivolts, close: Millivolts) {
+        self.lock().window_switch_voltages = (open, close);
+    }
+
+    /// Welds the relay contacts, a welded relay stays energised once it has been switched high
+    pub fn set_relays_welded(&self, opening: bool, closing: bool) {
+        let mut state = self.lock();
+        state.opening_relay_welded = opening;
+        state.closing_relay_welded = closing;
+    }
+
+    pub fn is_opening_relay_high(&self) -> bool {
+        self.lock().opening_relay
+    }
+
+    pub fn is_closing_relay_high(&self) -> bool {
+        self.lock().closing_relay
+    }
+
+    /// Whether both relays have ever been energised at the same time
+    pub fn relay_overlap_detected(&self) -> bool {
+        self.lock().relay_overlap_detected
+    }
+
+    fn lock(&self) -> MutexGuard<'_, SimulatedWindowState> {
+        self.state.lock().expect("Simulated window state poisoned")
+    }
+}
+
+pub struct SimulatedRelays {
+    window: SimulatedWindow,
+}
+
+impl SimulatedRelays {
+    fn set(&mut self, update: impl FnOnce(&mut SimulatedWindowState)) -> Result<(), HalError> {
+        let mut state = self.window.lock();
+        update(&mut state);
+
+        if state.opening_relay && state.closing_relay {
+            state.relay_overlap_detected = true;
+        }
+
+        Ok(())
+    }
+}
+
+impl RelayOutputs for SimulatedRelays {
+    fn set_open_high(&mut self) -> Result<(), HalError> {
+        self.set(|state| state.opening_relay = true)
+    }
+
+    fn set_open_low(&mut self) -> Result<(), HalError> {
+        self.set(|state| state.opening_relay = state.opening_relay && state.opening_relay_welded)
+    }
+
+    fn set_close_high(&mut self) -> Result<(), HalError> {
+        self.set(|state| state.closing_relay = true)
+    }
+
+    fn set_close_low(&mut self) -> Result<(), HalError> {
+        self.set(|state| state.closing_relay = state.closing_relay && state.closing_relay_welded)
+    }
+}
+
+pub struct SimulatedCurrentSense {
+    window: SimulatedWindow,
+}
+
+impl CurrentSenseInputs for SimulatedCurrentSense {
+    fn read_closing_voltage_drop(&mut self) -> Result<Millivolts, HalError> {
+        let state = self.window.lock();
+
+        Ok(state.sense_voltage(state.closing_current()))
+    }
+
+    fn read_opening_voltage_drop(&mut self) -> Result<Millivolts, HalError> {
+        let state = self.window.lock();
+
+        Ok(state.sense_voltage(state.opening_current()))
+    }
+
+    fn drain_closing_voltage_drops(&mut self, samples: &mut Vec<Millivolts>) -> Result<(), HalError> {
+        samples.extend(self.window.lock().closing_samples.drain(..));
+
+        Ok(())
+    }
+
+    fn drain_opening_voltage_drops(&mut self, samples: &mut Vec<Millivolts>) -> Result<(), HalError> {
+        samples.extend(self.window.lock().opening_samples.drain(..));
+
+        Ok(())
+    }
+
+    fn skip_closing_voltage_drops(&mut self) {
+        self.window.lock().closing_samples.clear();
+    }
+
+    fn skip_opening_voltage_drops(&mut self) {
+        self.window.lock().opening_samples.clear();
+    }
+
+    fn arm_trip(&mut self, closing: Option<Millivolts>, opening: Option<Millivolts>) {
+        let mut state = self.window.lock();
+        state.closing_trip = closing;
+        state.opening_trip = opening;
+    }
+
+    fn trip_signal(&self) -> Arc<Notify> {
+        self.window.lock().trip.clone()
+    }
+}
+
+pub struct SimulatedDoorSwitch {
+    window: SimulatedWindow,
+}
+
+impl DoorSwitchInput for SimulatedDoorSwitch {
+    fn is_door_open(&mut self) -> Result<bool, HalError> {
+        Ok(self.window.lock().door_open)
+    }
+}
+
+pub struct SimulatedWindowSwitch {
+    window: SimulatedWindow,
+}
+
+impl WindowSwitchInput for SimulatedWindowSwitch {
+    fn read_switch_state(&mut self) -> Result<PowerWindowButtonState, HalError> {
+        let (open, close) = self.window.lock().window_switch_voltages;
+
+        Ok(get_state_for_voltages(open, close))
+    }
+}
+
+pub struct SimulatedClock {
+    window: SimulatedWindow,
+}
+
+impl Clock for SimulatedClock {
+    fn now_millis(&self) -> u128 {
+        self.window.lock().now_micros / 1000
+    }
+
+    fn delay_ms(&mut self, millis: u32) {
+        self.window.advance(millis);
+    }
+}
+
+/// In-memory persistent store, clones share their content like a flash partition would
+#[derive(Clone, Default)]
+pub struct SimulatedStore {
+    entries: Arc<Mutex<HashMap<String, Vec<u8>>>>,
+}
+
+impl SimulatedStore {
+    fn entries(&self) -> Result<MutexGuard<'_, HashMap<String, Vec<u8>>>, HalError> {
+        self.entries.lock().map_err(|_| HalError::Simulated("Store poisoned"))
+    }
+}
+
+impl PersistentStore for SimulatedStore {
+    fn load(&mut self, key: &str, buffer: &mut [u8]) -> Result<bool, HalError> {
+        match self.entries()?.get(key) {
+            Some(data) if data.len() == buffer.len() => {
+                buffer.copy_from_slice(data);
+                Ok(true)
+            }
+            _ => Ok(false),
+        }
+    }
+
+    fn store(&mut self, key: &str, data: &[u8]) -> Result<(), HalError> {
+        self.entries()?.insert(key.to_string(), data.to_vec());
+
+        Ok(())
+    }
+
+    fn remove(&mut self, key: &str) -> Result<(), HalError> {
+        self.entries()?.remove(key);
+
+        Ok(())
+    }
+}
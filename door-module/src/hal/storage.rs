@@ -0,0 +1,46 @@
+use esp_idf_svc::nvs::{EspDefaultNvsPartition, EspNvs, NvsDefault};
+use esp_idf_sys::EspError;
+
+use super::traits::{HalError, PersistentStore};
+
+const NVS_NAMESPACE: &str = "door_module";
+
+pub struct NvsStore {
+    nvs: EspNvs<NvsDefault>,
+}
+
+impl PersistentStore for NvsStore {
+    fn load(&mut self, key: &str, buffer: &mut [u8]) -> Result<bool, HalError> {
+        let expected_length = buffer.len();
+
+        let length = match self.nvs.get_raw(key, buffer)? {
+            Some(data) => data.len(),
+            None => return Ok(false),
+        };
+
+        if length != expected_length {
+            log::warn!("Stored {} has {} bytes instead of {}, ignoring...", key, length, expected_length);
+            return Ok(false);
+        }
+
+        Ok(true)
+    }
+
+    fn store(&mut self, key: &str, data: &[u8]) -> Result<(), HalError> {
+        self.nvs.set_raw(key, data)?;
+
+        Ok(())
+    }
+
+    fn remove(&mut self, key: &str) -> Result<(), HalError> {
+        self.nvs.remove(key)?;
+
+        Ok(())
+    }
+}
+
+pub fn prepare_nvs_store(partition: EspDefaultNvsPartition) -> Result<NvsStore, EspError> {
+    Ok(NvsStore {
+        nvs: EspNvs::new(partition, NVS_NAMESPACE, true)?,
+    })
+}
@@ -0,0 +1,101 @@
+use std::sync::Arc;
+
+#[cfg(feature = "esp")]
+use esp_idf_sys::EspError;
+use shared_lib::{units::Millivolts, window_switch::PowerWindowButtonState};
+use tokio::sync::Notify;
+
+use super::reset::ResetRecord;
+
+#[derive(Debug)]
+pub enum HalError {
+    #[cfg(feature = "esp")]
+    Esp(EspError),
+    Simulated(&'static str),
+    /// A relay switch has been refused as it could energise both relays at once
+    Interlock(&'static str),
+    /// Motion has been refused because of a latched driver fault
+    DriverFault,
+}
+
+#[cfg(feature = "esp")]
+impl From<EspError> for HalError {
+    fn from(err: EspError) -> Self {
+        HalError::Esp(err)
+    }
+}
+
+/// Relay outputs switching the power window motor
+pub trait RelayOutputs: Send {
+    fn set_open_high(&mut self) -> Result<(), HalError>;
+
+    fn set_open_low(&mut self) -> Result<(), HalError>;
+
+    fn set_close_high(&mut self) -> Result<(), HalError>;
+
+    fn set_close_low(&mut self) -> Result<(), HalError>;
+}
+
+/// Current sense inputs of the power window motor
+pub trait CurrentSenseInputs: Send {
+    /// Reads the filtered voltage drop across the closing current sense resistor
+    fn read_closing_voltage_drop(&mut self) -> Result<Millivolts, HalError>;
+
+    /// Reads the filtered voltage drop across the opening current sense resistor
+    fn read_opening_voltage_drop(&mut self) -> Result<Millivolts, HalError>;
+
+    /// Appends the raw closing voltage drops sampled since the previous drain, oldest first
+    fn drain_closing_voltage_drops(&mut self, samples: &mut Vec<Millivolts>) -> Result<(), HalError>;
+
+    /// Appends the raw opening voltage drops sampled since the previous drain, oldest first
+    fn drain_opening_voltage_drops(&mut self, samples: &mut Vec<Millivolts>) -> Result<(), HalError>;
+
+    /// Drops the closing voltage drops sampled so far, the next drain starts after them
+    fn skip_closing_voltage_drops(&mut self);
+
+    /// Drops the opening voltage drops sampled so far, the next drain starts after them
+    fn skip_opening_voltage_drops(&mut self);
+
+    /// Compares the voltage drops against the given levels as they are sampled, `None` disarms a level.
+    /// A level exceeded notifies the trip signal and stays disarmed until it is armed again.
+    fn arm_trip(&mut self, closing: Option<Millivolts>, opening: Option<Millivolts>);
+
+    /// Notified whenever an armed level has been exceeded
+    fn trip_signal(&self) -> Arc<Notify>;
+}
+
+/// Door contact of a frameless window, telling whether the door is open
+pub trait DoorSwitchInput: Send {
+    fn is_door_open(&mut self) -> Result<bool, HalError>;
+}
+
+/// Two-stage window switch on the door itself
+pub trait WindowSwitchInput: Send {
+    fn read_switch_state(&mut self) -> Result<PowerWindowButtonState, HalError>;
+}
+
+/// Time source used by the driver and the services built on top of it
+pub trait Clock: Send {
+    /// Milliseconds since boot
+    fn now_millis(&self) -> u128;
+
+    /// Blocks for the given amount of milliseconds
+    fn delay_ms(&mut self, millis: u32);
+}
+
+/// Key-value storage for small records which have to survive a reboot
+pub trait PersistentStore: Send {
+    /// Fills `buffer` with the record stored under `key`, returns `false` if there is
+    /// no record or its size doesn't match the buffer
+    fn load(&mut self, key: &str, buffer: &mut [u8]) -> Result<bool, HalError>;
+
+    fn store(&mut self, key: &str, data: &[u8]) -> Result<(), HalError>;
+
+    fn remove(&mut self, key: &str) -> Result<(), HalError>;
+}
+
+/// Watches the control loop, resetting the system with both relays off once it stops checking in
+pub trait LoopWatchdog: Send {
+    /// Signals the loop is alive, keeping what it is doing for the report after a reset
+    fn feed(&mut self, record: ResetRecord);
+}
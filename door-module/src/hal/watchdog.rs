@@ -0,0 +1,115 @@
+use std::{
+    ptr::{addr_of, addr_of_mut},
+    time::Duration,
+};
+
+use esp_idf_hal::{
+    reset,
+    task::watchdog::{TWDTConfig, TWDTDriver, WatchdogSubscription, TWDT},
+};
+use esp_idf_sys::EspError;
+
+use super::{
+    reset::{ResetReason, ResetRecord, ResetReport},
+    traits::LoopWatchdog,
+};
+
+/// How long the control loop may go without checking in before the system is reset
+const WATCHDOG_TIMEOUT: Duration = Duration::from_secs(2);
+/// GPIOs of the closing and opening relays, see `PowerWindowDriverPins`
+const RELAY_GPIO_MASK: u32 = (1 << 10) | (1 << 11);
+/// `GPIO_OUT_W1TC_REG` of the ESP32-C6, outputs of the set bits are driven low
+const GPIO_OUT_W1TC_REG: *mut u32 = 0x6009_100C as *mut u32;
+
+/// Last record of the control loop, kept in memory which isn't cleared by a reset
+#[link_section = ".noinit"]
+static mut RESET_RECORD: u32 = 0;
+
+/// Task watchdog resetting the system with the relays off if the control loop stops feeding it
+pub struct TaskWatchdog {
+    subscription: WatchdogSubscription<'static>,
+}
+
+impl TaskWatchdog {
+    /// Starts watching the calling task, which has to be the one the control loop runs on
+    pub fn watch_current_task(twdt: TWDT) -> Result<TaskWatchdog, EspError> {
+        let driver = TWDTDriver::new(
+            twdt,
+            &TWDTConfig {
+                duration: WATCHDOG_TIMEOUT,
+                panic_on_trigger: true,
+                ..Default::default()
+            },
+        )?;
+
+        // The driver has to outlive the subscription, which lasts until the reset anyway
+        let driver: &'static mut TWDTDriver<'static> = Box::leak(Box::new(driver));
+
+        Ok(TaskWatchdog {
+            subscription: driver.watch_current_task()?,
+        })
+    }
+}
+
+impl LoopWatchdog for TaskWatchdog {
+    fn feed(&mut self, record: ResetRecord) {
+        // SAFETY: only the control loop writes the record, a single word is written at once
+        unsafe { addr_of_mut!(RESET_RECORD).write_volatile(record.to_word()) };
+
+        if let Err(err) = self.subscription.feed() {
+            log::error!("Couldn't feed the task watchdog: {:?}", err);
+        }
+    }
+}
+
+/// Switches both relays low right away, bypassing their drivers and the GPIO functions in flash
+#[inline(always)]
+fn force_relays_off() {
+    // SAFETY: the relay pins have been configured as outputs, clearing their levels has no other effect
+    unsafe { GPIO_OUT_W1TC_REG.write_volatile(RELAY_GPIO_MASK) };
+}
+
+/// Called by ESP-IDF from the task watchdog interrupt, before the system panics.
+/// Runs from IRAM as the flash cache may be disabled, so it must only write registers.
+#[no_mangle]
+#[link_section = ".iram1.wdt_user_handler"]
+extern "C" fn esp_task_wdt_isr_user_handler() {
+    force_relays_off();
+}
+
+/// Switches both relays low before a panic resets the system
+pub fn install_panic_hook() {
+    let default_hook = std::panic::take_hook();
+
+    std::panic::set_hook(Box::new(move |info| {
+        force_relays_off();
+        default_hook(info);
+    }));
+}
+
+/// Why the system started and what the control loop did right before, if it ran at all
+pub fn take_reset_report() -> ResetReport {
+    let reason = match reset::ResetReason::get() {
+        reset::ResetReason::PowerOn => ResetReason::PowerOn,
+        reset::ResetReason::Software => ResetReason::Software,
+        reset::ResetReason::Panic => ResetReason::Panic,
+        reset::ResetReason::Watchdog
+        | reset::ResetReason::InterruptWatchdog
+        | reset::ResetReason::TaskWatchdog => ResetReason::Watchdog,
+        reset::ResetReason::Brownout => ResetReason::Brownout,
+        _ => ResetReason::Other,
+    };
+
+    // SAFETY: read before the control loop starts writing the record
+    let word = unsafe { addr_of!(RESET_RECORD).read_volatile() };
+    unsafe { addr_of_mut!(RESET_RECORD).write_volatile(0) };
+
+    ResetReport {
+        reason,
+        record: match reason {
+            // No-init memory holds garbage after the power has been off
+            ResetReason::PowerOn => None,
+            _ => ResetRecord::from_word(word),
+        },
+    }
+}
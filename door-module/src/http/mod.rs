@@ -1 +1,2 @@
-pub mod server;
\ No newline at end of file
+pub mod server;
+pub mod telemetry;
\ No newline at end of file
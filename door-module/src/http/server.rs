@@ -1,20 +1,27 @@
 use embedded_svc::http::Method;
-use esp_idf_hal::task::block_on;
 use esp_idf_svc::http::server::EspHttpServer;
+use shared_lib::dto::{
+    pw_config::{Serialize, DTO_SIZE},
+    pw_status::PowerWindowStatus,
+    telemetry::TelemetryEvent,
+};
 use shared_lib::http::endpoints;
-use std::sync::Arc;
-use tokio::sync::{broadcast, Mutex};
+use tokio::sync::{broadcast, watch};
 
-use crate::app::{
-    events::{ServerRequest, ServerRequestType},
-    state::AppState,
+use crate::{
+    app::events::{ServerRequest, ServerRequestType},
+    svc::door_snapshot::DoorSnapshot,
 };
 
+use super::telemetry::prepare_telemetry_stream;
+
 const STACK_SIZE: usize = 10240;
 
 pub fn prepare_http_server<'a>(
     sender: broadcast::Sender<ServerRequest>,
-    app_state: Arc<Mutex<AppState>>,
+    snapshot_receiver: watch::Receiver<Option<DoorSnapshot>>,
+    pw_status_receiver: watch::Receiver<PowerWindowStatus>,
+    telemetry_receiver: broadcast::Receiver<TelemetryEvent>,
 ) -> EspHttpServer<'a> {
     log::info!("Spawned HTTP server task.");
 
@@ -24,18 +31,22 @@ pub fn prepare_http_server<'a>(
     })
     .unwrap();
 
+    let _snapshot_receiver = snapshot_receiver.clone();
     http_server
-        .fn_handler("/state", Method::Get, move |req| {
-            let app_state = app_state.clone();
+        .fn_handler(endpoints::DOOR_STATUS_PATH, Method::Get, move |req| {
+            // Copied out first, the control loop mustn't wait for the report to be put together
+            let snapshot = *_snapshot_receiver.borrow();
 
-            block_on(async move {
-                let app_state = app_state.lock().await;
+            let report = match snapshot {
+                Some(snapshot) => serde_json::to_vec(&snapshot.report())?,
+                None => {
+                    req.into_status_response(503)?.write(b"Status not published yet")?;
+                    return Ok(());
+                }
+            };
 
-                req.into_ok_response()
-                    .expect("hey")
-                    .write(format!("App state: {}", app_state.random_val).as_bytes())
-                    .expect("heya");
-            });
+            req.into_response(200, Some("OK"), &[("Content-Type", "application/json")])?
+                .write(&report)?;
 
             Ok(())
         })
@@ -114,7 +125,7 @@ pub fn prepare_http_server<'a>(
     let _sender = sender.clone();
     http_server
         .fn_handler(endpoints::CONFIGURE_WINDOWS_CURRENT_THRESHOLDS_PATH, Method::Post, move |mut req| {
-            let mut buffer: [u8; 8] = [0; 8];
+            let mut buffer: [u8; DTO_SIZE] = [0; DTO_SIZE];
 
             req.read(&mut buffer)?;
 
@@ -129,5 +140,111 @@ pub fn prepare_http_server<'a>(
         })
         .unwrap();
 
+    let _sender = sender.clone();
+    http_server
+        .fn_handler(endpoints::RESET_WINDOWS_CONFIG_PATH, Method::Post, move |req| {
+            _sender.send(ServerRequest {
+                request_type: ServerRequestType::ResetConfig,
+                request_data: Default::default(),
+            })?;
+
+            req.into_ok_response()?;
+
+            Ok(())
+        })
+        .unwrap();
+
+    let _sender = sender.clone();
+    http_server
+        .fn_handler(endpoints::CLEAR_WINDOWS_FAULT_PATH, Method::Post, move |mut req| {
+            let mut buffer: [u8; DTO_SIZE] = [0; DTO_SIZE];
+
+            // Without a body all faults are cleared
+            req.read(&mut buffer)?;
+
+            _sender.send(ServerRequest {
+                request_type: ServerRequestType::ClearFault,
+                request_data: buffer,
+            })?;
+
+            req.into_ok_response()?;
+
+            Ok(())
+        })
+        .unwrap();
+
+    let _sender = sender.clone();
+    http_server
+        .fn_handler(endpoints::MOVE_WINDOWS_TO_POSITION_PATH, Method::Post, move |mut req| {
+            let mut buffer: [u8; DTO_SIZE] = [0; DTO_SIZE];
+
+            req.read(&mut buffer)?;
+
+            _sender.send(ServerRequest {
+                request_type: ServerRequestType::MoveToPosition,
+                request_data: buffer,
+            })?;
+
+            req.into_ok_response()?;
+
+            Ok(())
+        })
+        .unwrap();
+
+    let _sender = sender.clone();
+    http_server
+        .fn_handler(endpoints::CALIBRATE_WINDOWS_PATH, Method::Post, move |req| {
+            _sender.send(ServerRequest {
+                request_type: ServerRequestType::Calibrate,
+                request_data: Default::default(),
+            })?;
+
+            req.into_ok_response()?;
+
+            Ok(())
+        })
+        .unwrap();
+
+    let _sender = sender.clone();
+    http_server
+        .fn_handler(endpoints::HEARTBEAT_PATH, Method::Post, move |mut req| {
+            let mut buffer: [u8; DTO_SIZE] = [0; DTO_SIZE];
+
+            req.read(&mut buffer)?;
+
+            _sender.send(ServerRequest {
+                request_type: ServerRequestType::Heartbeat,
+                request_data: buffer,
+            })?;
+
+            req.into_ok_response()?;
+
+            Ok(())
+        })
+        .unwrap();
+
+    http_server
+        .fn_handler(endpoints::WINDOWS_STATUS_PATH, Method::Get, move |req| {
+            let status = *pw_status_receiver.borrow();
+
+            req.into_ok_response()?.write(&status.serialize())?;
+
+            Ok(())
+        })
+        .unwrap();
+
+    http_server
+        .fn_handler(endpoints::WINDOWS_FAULTS_PATH, Method::Get, move |req| {
+            let snapshot = *snapshot_receiver.borrow();
+            let faults = snapshot.map(|snapshot| snapshot.fault_list()).unwrap_or_default();
+
+            req.into_ok_response()?.write(&faults.serialize())?;
+
+            Ok(())
+        })
+        .unwrap();
+
+    prepare_telemetry_stream(&mut http_server, telemetry_receiver).unwrap();
+
     return http_server;
 }
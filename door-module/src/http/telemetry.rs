@@ -0,0 +1,150 @@
+use std::sync::{Arc, Mutex};
+
+use embedded_svc::ws::FrameType;
+use esp_idf_svc::http::server::{
+    ws::{EspHttpWsConnection, EspHttpWsDetachedSender},
+    EspHttpServer,
+};
+use esp_idf_sys::EspError;
+use shared_lib::dto::telemetry::{TelemetryEvent, TelemetrySettings};
+use shared_lib::http::endpoints;
+use tokio::sync::broadcast;
+
+use crate::svc::telemetry::TelemetryThrottle;
+
+const STACK_SIZE: usize = 6144;
+/// Settings sent by a client are tiny, anything longer is refused
+const MAX_SETTINGS_SIZE: usize = 128;
+
+struct TelemetryClient {
+    sender: EspHttpWsDetachedSender,
+    throttle: TelemetryThrottle,
+}
+
+type TelemetryClients = Arc<Mutex<Vec<TelemetryClient>>>;
+
+/// Streams telemetry events to every WebSocket client connected to the telemetry endpoint.
+///
+/// Clients may send `TelemetrySettings` as a text frame to change their current sample interval.
+pub fn prepare_telemetry_stream(
+    http_server: &mut EspHttpServer<'_>,
+    receiver: broadcast::Receiver<TelemetryEvent>,
+) -> Result<(), EspError> {
+    let clients = TelemetryClients::default();
+
+    let handler_clients = clients.clone();
+    http_server.ws_handler(endpoints::TELEMETRY_PATH, move |ws: &mut EspHttpWsConnection| {
+        handle_connection(&handler_clients, ws)
+    })?;
+
+    std::thread::Builder::new()
+        .name("telemetry".into())
+        .stack_size(STACK_SIZE)
+        .spawn(move || stream(clients, receiver))
+        .map_err(|err| {
+            log::error!("Couldn't start telemetry stream: {:?}", err);
+            EspError::from_infallible::<{ esp_idf_sys::ESP_FAIL }>()
+        })?;
+
+    log::info!("Spawned telemetry stream.");
+
+    Ok(())
+}
+
+fn handle_connection(clients: &TelemetryClients, ws: &mut EspHttpWsConnection) -> Result<(), EspError> {
+    if ws.is_new() {
+        log::info!("Telemetry client {} connected.", ws.session());
+
+        let sender = ws.create_detached_sender()?;
+        lock(clients).push(TelemetryClient {
+            sender,
+            throttle: TelemetryThrottle::default(),
+        });
+
+        return Ok(());
+    }
+
+    if ws.is_closed() {
+        log::info!("Telemetry client {} disconnected.", ws.session());
+
+        let session = ws.session();
+        lock(clients).retain(|client| client.sender.session() != session);
+
+        return Ok(());
+    }
+
+    let mut buffer: [u8; MAX_SETTINGS_SIZE] = [0; MAX_SETTINGS_SIZE];
+    let (frame_type, length) = ws.recv(&mut buffer)?;
+
+    if !matches!(frame_type, FrameType::Text(false)) {
+        return Ok(());
+    }
+
+    // Text frames come with a terminating zero
+    let text = buffer[..length.min(MAX_SETTINGS_SIZE)].split(|byte| *byte == 0).next().unwrap_or_default();
+
+    let settings = match serde_json::from_slice::<TelemetrySettings>(text) {
+        Ok(settings) => settings,
+        Err(err) => {
+            log::warn!("Invalid telemetry settings from client {}: {:?}", ws.session(), err);
+            return Ok(());
+        }
+    };
+
+    log::info!("Telemetry client {} asked for {:?}.", ws.session(), settings);
+
+    let session = ws.session();
+    if let Some(client) = lock(clients).iter_mut().find(|client| client.sender.session() == session) {
+        client.throttle.set_sample_interval(settings.sample_interval);
+    }
+
+    Ok(())
+}
+
+fn stream(clients: TelemetryClients, mut receiver: broadcast::Receiver<TelemetryEvent>) {
+    loop {
+        let event = match receiver.blocking_recv() {
+            Ok(event) => event,
+            Err(broadcast::error::RecvError::Lagged(count)) => {
+                log::warn!("Telemetry stream lagged by {} events, skipping...", count);
+                continue;
+            }
+            Err(broadcast::error::RecvError::Closed) => {
+                log::warn!("Telemetry channel closed, stopping the stream.");
+                return;
+            }
+        };
+
+        // Sending waits for the HTTP server, which needs the clients to accept new connections
+        let mut senders: Vec<EspHttpWsDetachedSender> = lock(&clients)
+            .iter_mut()
+            .filter(|client| client.throttle.admit(&event))
+            .map(|client| client.sender.clone())
+            .collect();
+
+        if senders.is_empty() {
+            continue;
+        }
+
+        let frame = match serde_json::to_vec(&event) {
+            Ok(frame) => frame,
+            Err(err) => {
+                log::error!("Couldn't serialize telemetry event: {:?}", err);
+                continue;
+            }
+        };
+
+        for sender in senders.iter_mut() {
+            if let Err(err) = sender.send(FrameType::Text(false), &frame) {
+                log::warn!("Dropping telemetry client {}: {:?}", sender.session(), err);
+
+                let session = sender.session();
+                lock(&clients).retain(|client| client.sender.session() != session);
+            }
+        }
+    }
+}
+
+fn lock(clients: &TelemetryClients) -> std::sync::MutexGuard<'_, Vec<TelemetryClient>> {
+    clients.lock().expect("Telemetry clients poisoned")
+}
@@ -0,0 +1,7 @@
+pub mod app;
+pub mod hal;
+#[cfg(feature = "esp")]
+pub mod http;
+pub mod svc;
+
+pub const DEBUG: bool = true;
@@ -1,59 +1,74 @@
-use std::sync::Arc;
-
-use app::state::AppState;
+use door_module::app::events::ServerRequest;
+use door_module::hal::door_switch::prepare_door_switch;
+use door_module::hal::power_window_driver::{prepare_power_window_driver, PowerWindowDriverPins};
+use door_module::hal::storage::prepare_nvs_store;
+use door_module::hal::watchdog::{install_panic_hook, take_reset_report, TaskWatchdog};
+use door_module::http::server::prepare_http_server;
+use door_module::svc::power_windows::PowerWindowSvc;
 use esp_idf_hal::peripherals::Peripherals;
-use hal::power_window_driver::PowerWindowDriverPins;
-use http::server::prepare_http_server;
+use esp_idf_svc::nvs::EspDefaultNvsPartition;
 use shared_lib::dto::pw_config::PowerWindowsConfig;
 use shared_lib::system::{setup_system, run_tokio_runtime};
 use shared_lib::wifi::client::connect_wifi_sync;
 use shared_lib::wifi::config::{SYSTEM_AP_PASSWORD, SYSTEM_AP_SSID};
 use shared_lib::wifi::ext::get_sta_mac_address;
-use svc::power_windows::PowerWindowSvc;
-use tokio::sync::Mutex;
-
-use crate::app::events::ServerRequest;
-
-mod app;
-mod hal;
-mod http;
-mod svc;
-
-pub const DEBUG: bool = true;
 
 fn main() -> anyhow::Result<()> {
     setup_system()?;
+    install_panic_hook();
+
+    let reset_report = take_reset_report();
 
     let peripherals = Peripherals::take().expect("Couldn't take peripherals");
 
+    let nvs = EspDefaultNvsPartition::take()?;
+
     // Setup WI-FI AP and client connection
-    let mut wifi = connect_wifi_sync(peripherals.modem, SYSTEM_AP_SSID, SYSTEM_AP_PASSWORD)?;
+    let mut wifi = connect_wifi_sync(peripherals.modem, nvs.clone(), SYSTEM_AP_SSID, SYSTEM_AP_PASSWORD)?;
     
     let mac_address = get_sta_mac_address(&mut wifi)?;
 
     log::info!("Mac address: {:?}", mac_address);
 
-    let power_windows_svc = Arc::new(Mutex::new(PowerWindowSvc::new(
-        PowerWindowDriverPins {
-            adc: peripherals.adc1,
-            window_closing_sense_pin: peripherals.pins.gpio2,
-            window_opening_sense_pin: peripherals.pins.gpio3,
-            window_closing_pin: peripherals.pins.gpio10,
-            window_opening_pin: peripherals.pins.gpio11,
-        },
-        PowerWindowsConfig {
-            opening_current_interrupt_threshold_amps: 20,
-            closing_current_interrupt_threshold_amps: 20,
-            handle_time_threshold_millis: 300,
-        },
-    )?));
+    let (power_window_driver, window_switch) = prepare_power_window_driver(PowerWindowDriverPins {
+        adc: peripherals.adc1,
+        window_closing_sense_pin: peripherals.pins.gpio2,
+        window_opening_sense_pin: peripherals.pins.gpio3,
+        window_switch_open_pin: peripherals.pins.gpio4,
+        window_switch_close_pin: peripherals.pins.gpio5,
+        window_closing_pin: peripherals.pins.gpio10,
+        window_opening_pin: peripherals.pins.gpio11,
+    })?;
+
+    let door_switch = prepare_door_switch(peripherals.pins.gpio18)?;
+
+    // Thresholds are derived from the persisted calibration once the window has been calibrated
+    let mut power_windows_svc = PowerWindowSvc::new(
+        power_window_driver,
+        prepare_nvs_store(nvs)?,
+        PowerWindowsConfig::default(),
+    );
+
+    power_windows_svc.report_reset(reset_report);
+
+    let snapshot_receiver = power_windows_svc.subscribe_snapshot();
+    let pw_status_receiver = power_windows_svc.subscribe_status();
+    let telemetry_receiver = power_windows_svc.subscribe_telemetry();
+
+    // The runtime runs on this task, so does the control loop feeding the watchdog
+    let watchdog = TaskWatchdog::watch_current_task(peripherals.twdt)?;
 
     run_tokio_runtime(async move {
         let (sender, pw_svc_receiver) = tokio::sync::broadcast::channel::<ServerRequest>(8);
 
-        let http_server = prepare_http_server(sender.clone(), Arc::new(Mutex::new(AppState { random_val: "hey" })));
+        let http_server = prepare_http_server(
+            sender.clone(),
+            snapshot_receiver,
+            pw_status_receiver,
+            telemetry_receiver,
+        );
 
-        tokio::spawn(PowerWindowSvc::run_loop(pw_svc_receiver, power_windows_svc)).await.expect("Power window service crashed!");
+        tokio::spawn(PowerWindowSvc::run_loop(pw_svc_receiver, power_windows_svc, watchdog, door_switch, window_switch)).await.expect("Power window service crashed!");
 
         drop(http_server);
     })?;
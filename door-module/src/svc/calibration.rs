@@ -0,0 +1,131 @@
+use shared_lib::{
+    dto::pw_calibration::PowerWindowCalibration,
+    units::{Milliamps, Milliseconds},
+};
+
+use super::window_position::TravelDirection;
+
+/// How many times the running current the motor has to draw to count as stalled
+const STALL_CURRENT_FACTOR: u32 = 2;
+/// Running current samples needed before a stall can be detected
+const MIN_RUNNING_SAMPLES: u32 = 3;
+/// A single movement taking longer than this aborts the calibration
+const PHASE_TIMEOUT_MILLIS: u128 = 20000;
+/// How long to open before referencing, so the glass never starts out pressed against the upper end stop
+const CLEARING_MILLIS: u128 = 1000;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CalibrationPhase {
+    /// Opening for a short while to get away from the upper end stop
+    Clearing,
+    /// Closing fully to start measuring from a known position
+    Referencing,
+    /// Opening fully, measuring the way down
+    MeasuringOpening,
+    /// Closing fully, measuring the way up
+    MeasuringClosing,
+}
+
+#[derive(Debug, Clone, Copy)]
+pub enum CalibrationStep {
+    /// Keep the motor running
+    Continue,
+    /// The end stop has been reached, run the motor in the given direction next
+    NextPhase(TravelDirection),
+    /// The window is closed and has been measured
+    Finished(PowerWindowCalibration),
+    /// The calibration has to be aborted
+    Failed(&'static str),
+}
+
+/// Measures travel times and currents while the window is driven from
+/// end stop to end stop, without relying on any configured threshold.
+///
+/// End stops are detected by the current rising well above the running
+/// current measured during the same movement.
+pub struct CalibrationRun {
+    phase: CalibrationPhase,
+    phase_started_millis: u128,
+    running_sum: u32,
+    running_samples: u32,
+    result: PowerWindowCalibration,
+}
+
+impl CalibrationRun {
+    /// Starts the calibration, the motor has to be started opening right away
+    pub fn new(now_millis: u128) -> CalibrationRun {
+        CalibrationRun {
+            phase: CalibrationPhase::Clearing,
+            phase_started_millis: now_millis,
+            running_sum: 0,
+            running_samples: 0,
+            result: PowerWindowCalibration::default(),
+        }
+    }
+
+    /// Feeds a current sample taken after the inrush blanking window
+    pub fn sample(&mut self, current: Milliamps, now_millis: u128) -> CalibrationStep {
+        let elapsed_millis = now_millis.saturating_sub(self.phase_started_millis);
+
+        if elapsed_millis > PHASE_TIMEOUT_MILLIS {
+            return CalibrationStep::Failed("End stop not reached in time");
+        }
+
+        if self.phase == CalibrationPhase::Clearing {
+            if elapsed_millis < CLEARING_MILLIS {
+                return CalibrationStep::Continue;
+            }
+
+            self.start_phase(CalibrationPhase::Referencing, now_millis);
+            return CalibrationStep::NextPhase(TravelDirection::Closing);
+        }
+
+        let stalled = self.running_samples >= MIN_RUNNING_SAMPLES
+            && current.0 as u32 > self.running_current().0 as u32 * STALL_CURRENT_FACTOR;
+
+        if !stalled {
+            self.running_sum += current.0 as u32;
+            self.running_samples += 1;
+            return CalibrationStep::Continue;
+        }
+
+        let travel_time = Milliseconds::checked_from_millis(elapsed_millis).unwrap_or(Milliseconds::MAX);
+        let running_current = self.running_current();
+
+        match self.phase {
+            CalibrationPhase::Clearing | CalibrationPhase::Referencing => {
+                self.start_phase(CalibrationPhase::MeasuringOpening, now_millis);
+                CalibrationStep::NextPhase(TravelDirection::Opening)
+            }
+            CalibrationPhase::MeasuringOpening => {
+                self.result.opening_travel_time = travel_time;
+                self.result.opening_running_current = running_current;
+                self.result.opening_stall_current = current;
+
+                self.start_phase(CalibrationPhase::MeasuringClosing, now_millis);
+                CalibrationStep::NextPhase(TravelDirection::Closing)
+            }
+            CalibrationPhase::MeasuringClosing => {
+                self.result.closing_travel_time = travel_time;
+                self.result.closing_running_current = running_current;
+                self.result.closing_stall_current = current;
+
+                CalibrationStep::Finished(self.result)
+            }
+        }
+    }
+
+    fn running_current(&self) -> Milliamps {
+        match self.running_samples {
+            0 => Milliamps::ZERO,
+            samples => Milliamps((self.running_sum / samples) as u16),
+        }
+    }
+
+    fn start_phase(&mut self, phase: CalibrationPhase, now_millis: u128) {
+        self.phase = phase;
+        self.phase_started_millis = now_millis;
+        self.running_sum = 0;
+        self.running_samples = 0;
+    }
+}
@@ -0,0 +1,84 @@
+use shared_lib::dto::{
+    door_status::{CurrentReport, DoorStatusReport, FaultReport},
+    pw_config::PowerWindowsConfig,
+    pw_faults::{PowerWindowFault, PowerWindowFaults, MAX_REPORTED_FAULTS},
+};
+
+use super::{
+    fault_manager::{FaultCode, FaultEntry},
+    obstruction_detector::StopCause,
+    state_machine::State,
+    window_switch::MovementSource,
+};
+
+const FIRMWARE_VERSION: &str = env!("CARGO_PKG_VERSION");
+
+/// Everything the status report and the fault list are built from, copied out of the control loop.
+///
+/// Publishing it doesn't allocate, the reports are only put together when
+/// somebody asks for them.
+#[derive(Debug, Clone, Copy)]
+pub struct DoorSnapshot {
+    pub uptime_millis: u128,
+    pub state: State,
+    pub position_percent: Option<u8>,
+    pub currents: CurrentReport,
+    pub last_stop_cause: Option<StopCause>,
+    pub config: PowerWindowsConfig,
+    /// Kept faults in the order they have been raised, there is at most one per code
+    pub faults: [Option<FaultEntry>; FaultCode::ALL.len()],
+    pub link_lost: bool,
+    pub link_lost_millis: Option<u128>,
+    pub movement_source: Option<MovementSource>,
+    pub passenger_lockout: bool,
+}
+
+impl DoorSnapshot {
+    pub fn faults(&self) -> impl Iterator<Item = &FaultEntry> {
+        self.faults.iter().flatten()
+    }
+
+    /// Collects everything worth knowing about the door module into a single report
+    pub fn report(&self) -> DoorStatusReport {
+        DoorStatusReport {
+            firmware_version: FIRMWARE_VERSION.to_string(),
+            uptime_millis: self.uptime_millis as u64,
+            state: format!("{:?}", self.state),
+            state_code: self.state as u8,
+            position_percent: self.position_percent,
+            currents: self.currents,
+            last_stop_cause: self.last_stop_cause.map(|cause| format!("{:?}", cause)),
+            config: self.config,
+            faults: self
+                .faults()
+                .map(|entry| FaultReport {
+                    code: format!("{:?}", entry.code),
+                    code_value: entry.code as u8,
+                    severity: format!("{:?}", entry.code.severity()),
+                    status: format!("{:?}", entry.status),
+                    occurrences: entry.occurrences,
+                })
+                .collect(),
+            link_lost: self.link_lost,
+            link_lost_at_uptime_millis: self.link_lost_millis.map(|millis| millis as u64),
+            movement_source: self.movement_source.map(|source| format!("{:?}", source)),
+            passenger_lockout: self.passenger_lockout,
+        }
+    }
+
+    /// Kept faults as served to the main server, as many as fit into a single DTO
+    pub fn fault_list(&self) -> PowerWindowFaults {
+        PowerWindowFaults {
+            faults: self
+                .faults()
+                .take(MAX_REPORTED_FAULTS)
+                .map(|entry| PowerWindowFault {
+                    code: entry.code as u8,
+                    severity: entry.code.severity() as u8,
+                    status: entry.status as u8,
+                    occurrences: entry.occurrences.min(u8::MAX as u16) as u8,
+                })
+                .collect(),
+        }
+    }
+}
@@ -0,0 +1,32 @@
+/// How long a new reading of the door switch has to hold before it counts
+const DEBOUNCE_MILLIS: u128 = 100;
+
+/// Debounces the door switch, reporting each settled change once.
+///
+/// The first settled reading counts as a change as well, a door which is
+/// already open at boot is treated as just opened.
+#[derive(Default)]
+pub struct DoorSwitchMonitor {
+    door_open: Option<bool>,
+    changing_since_millis: Option<u128>,
+}
+
+impl DoorSwitchMonitor {
+    /// Feeds a reading, returns whether the door is open once a change has settled
+    pub fn update(&mut self, door_open: bool, now_millis: u128) -> Option<bool> {
+        if self.door_open == Some(door_open) {
+            self.changing_since_millis = None;
+            return None;
+        }
+
+        let changing_since_millis = *self.changing_since_millis.get_or_insert(now_millis);
+
+        if now_millis.saturating_sub(changing_since_millis) < DEBOUNCE_MILLIS {
+            return None;
+        }
+
+        self.door_open = Some(door_open);
+        self.changing_since_millis = None;
+        Some(door_open)
+    }
+}
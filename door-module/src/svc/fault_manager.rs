@@ -0,0 +1,208 @@
+use crate::hal::power_window_driver::DriverFault;
+
+/// How long the ADC has to read fine again before its fault clears itself
+const ADC_FAILURE_HOLDOFF_MILLIS: u128 = 2000;
+/// How long an unexpected transition stays reported after it happened
+const UNEXPECTED_TRANSITION_HOLDOFF_MILLIS: u128 = 5000;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum FaultCode {
+    /// Current flows through a relay which has been switched low
+    RelayWelded = 1,
+    /// A relay couldn't be switched, its state is unknown
+    RelaySwitchFailed = 2,
+    /// The current sense inputs couldn't be read
+    AdcFailure = 3,
+    /// The measured current doesn't match what the commanded relays allow
+    ImplausibleCurrent = 4,
+    /// The window was interrupted in a state it can't be interrupted in
+    UnexpectedTransition = 5,
+    /// The system was reset unexpectedly while the motor was running
+    UnexpectedReset = 6,
+    /// The main server's heartbeats have stopped
+    LinkLost = 7,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Severity {
+    /// Reported only, the window keeps working
+    Warning = 1,
+    /// Both relays are switched off and motion is refused while the fault is present
+    Critical = 2,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Latching {
+    /// Stays present until it is cleared by command
+    Latched,
+    /// Clears itself once its condition has been gone for the given time
+    SelfClearing(u128),
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum FaultStatus {
+    /// The condition is present right now
+    Active = 1,
+    /// The condition is gone, but the fault is kept until it clears
+    Stored = 2,
+}
+
+impl FaultCode {
+    pub const ALL: [FaultCode; 7] = [
+        FaultCode::RelayWelded,
+        FaultCode::RelaySwitchFailed,
+        FaultCode::AdcFailure,
+        FaultCode::ImplausibleCurrent,
+        FaultCode::UnexpectedTransition,
+        FaultCode::UnexpectedReset,
+        FaultCode::LinkLost,
+    ];
+
+    pub fn from_raw(raw: u8) -> Option<FaultCode> {
+        FaultCode::ALL.into_iter().find(|code| *code as u8 == raw)
+    }
+
+    pub fn severity(&self) -> Severity {
+        match self {
+            FaultCode::UnexpectedTransition | FaultCode::UnexpectedReset | FaultCode::LinkLost => Severity::Warning,
+            _ => Severity::Critical,
+        }
+    }
+
+    pub fn latching(&self) -> Latching {
+        match self {
+            FaultCode::AdcFailure => Latching::SelfClearing(ADC_FAILURE_HOLDOFF_MILLIS),
+            FaultCode::UnexpectedTransition => Latching::SelfClearing(UNEXPECTED_TRANSITION_HOLDOFF_MILLIS),
+            _ => Latching::Latched,
+        }
+    }
+
+    /// Whether the driver latches this fault as well, clearing it has to clear the driver too
+    pub fn is_driver_fault(&self) -> bool {
+        matches!(self, FaultCode::RelayWelded | FaultCode::RelaySwitchFailed)
+    }
+}
+
+impl From<DriverFault> for FaultCode {
+    fn from(fault: DriverFault) -> Self {
+        match fault {
+            DriverFault::RelayWelded => FaultCode::RelayWelded,
+            DriverFault::RelaySwitchFailed => FaultCode::RelaySwitchFailed,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy)]
+pub struct FaultEntry {
+    pub code: FaultCode,
+    pub status: FaultStatus,
+    /// How often the condition has been raised since the fault was last cleared
+    pub occurrences: u16,
+    /// When the condition was last raised or went away
+    pub changed_millis: u128,
+}
+
+/// Keeps the faults of the door module, one entry per fault code.
+///
+/// A fault is active while its condition is present and stored once it has
+/// gone away. Stored faults stay until they are cleared by command, or
+/// until their holdoff has passed if they clear themselves.
+#[derive(Default)]
+pub struct FaultManager {
+    entries: Vec<FaultEntry>,
+}
+
+impl FaultManager {
+    /// Marks the condition of a fault as present, returns whether it wasn't active before
+    pub fn raise(&mut self, code: FaultCode, now_millis: u128) -> bool {
+        let entry = match self.entries.iter_mut().find(|entry| entry.code == code) {
+            Some(entry) => entry,
+            None => {
+                self.entries.push(FaultEntry {
+                    code,
+                    status: FaultStatus::Stored,
+                    occurrences: 0,
+                    changed_millis: now_millis,
+                });
+                self.entries.last_mut().expect("Fault entry just pushed")
+            }
+        };
+
+        if entry.status == FaultStatus::Active {
+            return false;
+        }
+
+        log::error!("Fault {:?} raised ({:?}).", code, code.severity());
+
+        entry.status = FaultStatus::Active;
+        entry.occurrences = entry.occurrences.saturating_add(1);
+        entry.changed_millis = now_millis;
+
+        true
+    }
+
+    /// Marks the condition of a fault as gone, the fault is kept until it clears
+    pub fn resolve(&mut self, code: FaultCode, now_millis: u128) {
+        if let Some(entry) = self
+            .entries
+            .iter_mut()
+            .find(|entry| entry.code == code && entry.status == FaultStatus::Active)
+        {
+            log::info!("Fault {:?} condition gone.", code);
+
+            entry.status = FaultStatus::Stored;
+            entry.changed_millis = now_millis;
+        }
+    }
+
+    /// Drops stored faults whose holdoff has passed
+    pub fn update(&mut self, now_millis: u128) {
+        self.entries.retain(|entry| {
+            let expired = match (entry.status, entry.code.latching()) {
+                (FaultStatus::Stored, Latching::SelfClearing(holdoff_millis)) => {
+                    now_millis.saturating_sub(entry.changed_millis) >= holdoff_millis
+                }
+                _ => false,
+            };
+
+            if expired {
+                log::info!("Fault {:?} cleared itself.", entry.code);
+            }
+
+            !expired
+        });
+    }
+
+    /// Clears the given fault or all of them, returns the cleared codes
+    pub fn clear(&mut self, code: Option<FaultCode>) -> Vec<FaultCode> {
+        let mut cleared = Vec::new();
+
+        self.entries.retain(|entry| match code {
+            Some(code) if entry.code != code => true,
+            _ => {
+                cleared.push(entry.code);
+                false
+            }
+        });
+
+        cleared
+    }
+
+    /// Whether the window has to stay in its safe state
+    pub fn is_blocking(&self) -> bool {
+        self.entries
+            .iter()
+            .any(|entry| entry.code.severity() == Severity::Critical)
+    }
+
+    /// The fault to report first, active ones before stored ones of the same severity
+    pub fn most_severe(&self) -> Option<&FaultEntry> {
+        self.entries
+            .iter()
+            .max_by_key(|entry| (entry.code.severity(), entry.status == FaultStatus::Active))
+    }
+
+    pub fn entries(&self) -> &[FaultEntry] {
+        &self.entries
+    }
+}
@@ -0,0 +1,59 @@
+use shared_lib::units::{Milliamps, Milliseconds};
+
+/// Start-up current of a single movement
+#[derive(Debug, Clone, Copy, Default)]
+pub struct InrushRecord {
+    /// Highest current seen during the blanking window
+    pub peak_current: Milliamps,
+    /// Time from the relay change until the current dropped below the interrupt threshold
+    pub duration: Milliseconds,
+}
+
+/// Tracks the inrush spike after each relay change.
+///
+/// While blanking, the regular interrupt thresholds and the obstruction
+/// detector are bypassed and only the inrush limit may stop the motor.
+#[derive(Default)]
+pub struct InrushMonitor {
+    started_millis: Option<u128>,
+    settled: bool,
+    record: InrushRecord,
+}
+
+impl InrushMonitor {
+    /// Marks the relays as switched now, starting a new record
+    pub fn start(&mut self, now_millis: u128) {
+        self.started_millis = Some(now_millis);
+        self.settled = false;
+        self.record = InrushRecord::default();
+    }
+
+    /// Whether a sample taken now still falls into the blanking window
+    pub fn is_blanking(&self, blanking_time: Milliseconds, now_millis: u128) -> bool {
+        self.started_millis
+            .is_some_and(|started_millis| now_millis.saturating_sub(started_millis) < blanking_time.as_millis())
+    }
+
+    /// Feeds a current sample taken during the blanking window
+    pub fn sample(&mut self, current: Milliamps, threshold: Milliamps, now_millis: u128) {
+        let started_millis = match self.started_millis {
+            Some(started_millis) => started_millis,
+            None => return,
+        };
+
+        self.record.peak_current = self.record.peak_current.max(current);
+
+        if self.settled {
+            return;
+        }
+
+        self.record.duration =
+            Milliseconds::checked_from_millis(now_millis.saturating_sub(started_millis)).unwrap_or(Milliseconds::MAX);
+        self.settled = current <= threshold;
+    }
+
+    /// Start-up current of the last movement
+    pub fn record(&self) -> InrushRecord {
+        self.record
+    }
+}
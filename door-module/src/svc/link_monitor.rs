@@ -0,0 +1,54 @@
+use shared_lib::units::Milliseconds;
+
+/// Watches the heartbeats of the main server.
+///
+/// The link counts as lost once no heartbeat has arrived for the timeout and
+/// as back with the next heartbeat. The watch starts when the monitor is
+/// created, a main server which never shows up is lost after the timeout too.
+pub struct LinkMonitor {
+    /// Last heartbeat, or when the watch started if there hasn't been any yet
+    last_heartbeat_millis: u128,
+    lost: bool,
+    /// When the link was last lost, kept after it is back
+    last_lost_millis: Option<u128>,
+}
+
+impl LinkMonitor {
+    pub fn new(now_millis: u128) -> LinkMonitor {
+        LinkMonitor {
+            last_heartbeat_millis: now_millis,
+            lost: false,
+            last_lost_millis: None,
+        }
+    }
+
+    /// Records a heartbeat, returns whether it ends link-loss mode
+    pub fn heartbeat(&mut self, now_millis: u128) -> bool {
+        self.last_heartbeat_millis = now_millis;
+
+        let recovered = self.lost;
+        self.lost = false;
+        recovered
+    }
+
+    /// Checks whether the heartbeats have stopped, returns whether the link has just been lost
+    pub fn check(&mut self, timeout: Milliseconds, now_millis: u128) -> bool {
+        let timed_out = now_millis.saturating_sub(self.last_heartbeat_millis) >= timeout.as_millis();
+
+        if !timed_out || self.lost {
+            return false;
+        }
+
+        self.lost = true;
+        self.last_lost_millis = Some(now_millis);
+        true
+    }
+
+    pub fn is_lost(&self) -> bool {
+        self.lost
+    }
+
+    pub fn last_lost_millis(&self) -> Option<u128> {
+        self.last_lost_millis
+    }
+}
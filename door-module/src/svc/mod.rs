@@ -1 +1,15 @@
-pub mod power_windows;
\ No newline at end of file
+pub mod calibration;
+pub mod door_snapshot;
+pub mod door_switch;
+pub mod fault_manager;
+pub mod inrush_monitor;
+pub mod link_monitor;
+pub mod obstruction_detector;
+pub mod power_windows;
+pub mod ripple_counter;
+pub mod settings;
+pub mod state_machine;
+pub mod telemetry;
+pub mod thermal_model;
+pub mod window_position;
+pub mod window_switch;
\ No newline at end of file
@@ -0,0 +1,192 @@
+use std::collections::VecDeque;
+
+use shared_lib::units::Milliamps;
+
+use super::window_position::TravelDirection;
+
+/// Amount of position bins the learned current baseline is split into
+const BASELINE_BINS: usize = 20;
+/// Amount of samples the current slope is computed over
+const SLOPE_WINDOW: usize = 4;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum StopCause {
+    /// The glass has reached the end of its travel
+    EndOfTravel = 1,
+    /// The current rose sharply mid-travel, something is trapped
+    Obstruction = 2,
+    /// The motor is stuck mid-travel without a sudden current rise
+    Stall = 3,
+    /// The current threshold has been exceeded while the position was unknown
+    OverCurrent = 4,
+    /// The motor thermal model asked for the motor to cool down
+    Overheated = 5,
+    /// The main server's heartbeats have stopped
+    LinkLost = 6,
+}
+
+impl StopCause {
+    pub const ALL: [StopCause; 6] = [
+        StopCause::EndOfTravel,
+        StopCause::Obstruction,
+        StopCause::Stall,
+        StopCause::OverCurrent,
+        StopCause::Overheated,
+        StopCause::LinkLost,
+    ];
+}
+
+#[derive(Debug, Clone, Copy)]
+pub struct ObstructionDetectorConfig {
+    /// How far above the learned baseline the current has to be to count as obstruction
+    pub obstruction_margin: Milliamps,
+    /// How fast the current has to rise to count as obstruction, in mA/s
+    pub obstruction_slope_milliamps_per_sec: u32,
+    /// Fraction of the travel at either end which counts as end of travel
+    pub end_zone: f32,
+}
+
+impl Default for ObstructionDetectorConfig {
+    fn default() -> Self {
+        ObstructionDetectorConfig {
+            obstruction_margin: Milliamps(2000),
+            obstruction_slope_milliamps_per_sec: 10000,
+            end_zone: 0.03,
+        }
+    }
+}
+
+/// Running current learned per direction and position bin
+struct CurrentBaseline {
+    opening: [Option<Milliamps>; BASELINE_BINS],
+    closing: [Option<Milliamps>; BASELINE_BINS],
+}
+
+impl CurrentBaseline {
+    fn bins(&mut self, direction: TravelDirection) -> &mut [Option<Milliamps>; BASELINE_BINS] {
+        match direction {
+            TravelDirection::Opening => &mut self.opening,
+            TravelDirection::Closing => &mut self.closing,
+        }
+    }
+
+    fn bin_for_position(position: f32) -> usize {
+        ((position * BASELINE_BINS as f32) as usize).min(BASELINE_BINS - 1)
+    }
+
+    fn get(&mut self, direction: TravelDirection, position: f32) -> Option<Milliamps> {
+        self.bins(direction)[Self::bin_for_position(position)]
+    }
+
+    fn learn(&mut self, direction: TravelDirection, position: f32, current: Milliamps) {
+        let bin = &mut self.bins(direction)[Self::bin_for_position(position)];
+
+        // Exponential moving average with a weight of 1/8 for the new sample
+        *bin = Some(match *bin {
+            Some(baseline) => Milliamps(((baseline.0 as u32 * 7 + current.0 as u32) / 8) as u16),
+            None => current,
+        });
+    }
+}
+
+/// Decides when a running motor has to be stopped and why, based on the
+/// current slope and the current learned for the glass position.
+pub struct ObstructionDetector {
+    config: ObstructionDetectorConfig,
+    baseline: CurrentBaseline,
+    samples: VecDeque<(u128, Milliamps)>,
+}
+
+impl ObstructionDetector {
+    pub fn new(config: ObstructionDetectorConfig) -> ObstructionDetector {
+        ObstructionDetector {
+            config,
+            baseline: CurrentBaseline {
+                opening: [None; BASELINE_BINS],
+                closing: [None; BASELINE_BINS],
+            },
+            samples: VecDeque::with_capacity(SLOPE_WINDOW),
+        }
+    }
+
+    /// Forgets the samples of the previous movement, the learned baseline is kept
+    pub fn reset(&mut self) {
+        self.samples.clear();
+    }
+
+    /// Feeds a current sample of the running motor, returns why the motor
+    /// has to be stopped or `None` if it may keep running
+    pub fn sample(
+        &mut self,
+        direction: TravelDirection,
+        position: Option<f32>,
+        current: Milliamps,
+        threshold: Milliamps,
+        now_millis: u128,
+    ) -> Option<StopCause> {
+        if self.samples.len() == SLOPE_WINDOW {
+            self.samples.pop_front();
+        }
+        self.samples.push_back((now_millis, current));
+
+        let position = match position {
+            Some(position) => position,
+            None => return (current > threshold).then_some(StopCause::OverCurrent),
+        };
+
+        // Anti-pinch only matters while the glass moves up
+        if direction == TravelDirection::Closing
+            && !self.is_at_end_of_travel(direction, position)
+            && self.is_obstructed(direction, position, current)
+        {
+            return Some(StopCause::Obstruction);
+        }
+
+        if current > threshold {
+            return Some(self.over_threshold_cause(direction, Some(position)));
+        }
+
+        self.baseline.learn(direction, position, current);
+
+        None
+    }
+
+    /// Why a current above the threshold stops the motor at the given position
+    pub fn over_threshold_cause(&self, direction: TravelDirection, position: Option<f32>) -> StopCause {
+        match position {
+            Some(position) if self.is_at_end_of_travel(direction, position) => StopCause::EndOfTravel,
+            Some(_) => StopCause::Stall,
+            None => StopCause::OverCurrent,
+        }
+    }
+
+    fn is_at_end_of_travel(&self, direction: TravelDirection, position: f32) -> bool {
+        match direction {
+            TravelDirection::Opening => position >= 1.0 - self.config.end_zone,
+            TravelDirection::Closing => position <= self.config.end_zone,
+        }
+    }
+
+    fn is_obstructed(&mut self, direction: TravelDirection, position: f32, current: Milliamps) -> bool {
+        let baseline = match self.baseline.get(direction, position) {
+            Some(baseline) => baseline,
+            None => return false,
+        };
+
+        if current <= baseline.saturating_add(self.config.obstruction_margin) {
+            return false;
+        }
+
+        self.slope_milliamps_per_sec()
+            .is_some_and(|slope| slope >= self.config.obstruction_slope_milliamps_per_sec as i64)
+    }
+
+    fn slope_milliamps_per_sec(&self) -> Option<i64> {
+        let (first_millis, first_current) = *self.samples.front()?;
+        let (last_millis, last_current) = *self.samples.back()?;
+
+        let elapsed_millis = last_millis.checked_sub(first_millis).filter(|millis| *millis > 0)?;
+
+        Some((last_current.0 as i64 - first_current.0 as i64) * 1000 / elapsed_millis as i64)
+    }
+}
@@ -1,374 +1,1328 @@
-use std::{sync::Arc, time::Duration};
-
-use esp_idf_svc::systime::EspSystemTime;
-use esp_idf_sys::EspError;
-use shared_lib::dto::pw_config::{PowerWindowsConfig, Deserialize};
-use tokio::{
-    join,
-    sync::{broadcast, Mutex},
+use std::time::Duration;
+
+use shared_lib::dto::{
+    door_status::CurrentReport,
+    pw_calibration::PowerWindowCalibration,
+    pw_config::{LinkLossPolicy, PowerWindowsConfig, Deserialize, DTO_SIZE},
+    pw_faults::ClearFaultRequest,
+    pw_lockout::PassengerLockoutState,
+    pw_position::MoveToPositionRequest,
+    pw_status::PowerWindowStatus,
+    telemetry::TelemetryEvent,
 };
+use shared_lib::units::{Milliamps, Milliseconds};
+use shared_lib::window_switch::PowerWindowButtonState;
+use tokio::sync::{broadcast, watch};
 
 use crate::{
     app::events::{ServerRequest, ServerRequestType},
-    hal::power_window_driver::{PowerWindowDriver, PowerWindowDriverPins},
+    hal::{
+        current_sensor::CurrentSensorConfig,
+        power_window_driver::{PowerWindowDriver, WindowCurrentState, WindowDriverState},
+        reset::{ResetRecord, ResetReport},
+        traits::{
+            Clock, CurrentSenseInputs, DoorSwitchInput, HalError, LoopWatchdog, PersistentStore, RelayOutputs,
+            WindowSwitchInput,
+        },
+    },
+};
+
+use super::{
+    calibration::{CalibrationRun, CalibrationStep},
+    door_snapshot::DoorSnapshot,
+    door_switch::DoorSwitchMonitor,
+    fault_manager::{FaultCode, FaultManager, Severity},
+    inrush_monitor::InrushMonitor,
+    link_monitor::LinkMonitor,
+    obstruction_detector::{ObstructionDetector, ObstructionDetectorConfig, StopCause},
+    ripple_counter::RippleCounter,
+    settings::{self, CALIBRATION_KEY, CONFIG_KEY, LOCKOUT_KEY},
+    state_machine::{transition, Action, Input},
+    thermal_model::{MotorThermalModel, ThermalLimits},
+    window_position::{TravelDirection, WindowPositionEstimator, WindowTravelConfig},
+    window_switch::{MovementSource, WindowSwitchMonitor},
 };
 
+pub use super::state_machine::State;
+
+/// Telemetry events kept for slow subscribers, about a second of current samples
+const TELEMETRY_CHANNEL_SIZE: usize = 32;
+
+/// Interval of the control loop, current trips are handled in between as they happen
+const CONTROL_INTERVAL: Duration = Duration::from_millis(50);
+
+/// How close the estimated position has to be to a target to count as reached
+const POSITION_TOLERANCE: f32 = 0.01;
+/// How far open the glass has to be to be clear of the seal, it isn't dropped any further then
+const SEAL_CLEARANCE: f32 = 0.05;
+/// Current the energised relay has to carry at least once the inrush is over
+const MIN_RUNNING_CURRENT: Milliamps = Milliamps(100);
+/// How long the running motor may draw less than the minimum before its current is implausible
+const MISSING_CURRENT_TIME: Milliseconds = Milliseconds(500);
+
+/// Where the reversal after a closing obstruction ends
 #[derive(Debug, Clone, Copy)]
-pub enum State {
-    None = 0,
-    Stopped = 0b0001,
-
-    OpeningContinuous = 0b0010,
-    OpeningFully = 0b0110,
-    OpeningInterrupted = 0b0011,
-    OpeningFinished = 0b0111,
-
-    ClosingContinuous = 0b1010,
-    ClosingFully = 0b1110,
-    ClosingInterrupted = 0b1011,
-    ClosingFinished = 0b1111,
+enum ReversalTarget {
+    Position(f32),
+    Deadline(u128),
 }
 
-pub struct PowerWindowSvc {
-    window_driver: PowerWindowDriver,
+pub struct PowerWindowSvc<TOutput, TInput, TClock, TStore>
+where
+    TOutput: RelayOutputs,
+    TInput: CurrentSenseInputs,
+    TClock: Clock,
+    TStore: PersistentStore,
+{
+    window_driver: PowerWindowDriver<TOutput, TInput, TClock>,
+    store: TStore,
 
     last_handle_time_millis: u128,
     state: State,
     config: PowerWindowsConfig,
+    /// Config restored by a reset, before the calibration is applied
+    default_config: PowerWindowsConfig,
+
+    position: WindowPositionEstimator,
+    target_position: Option<f32>,
+    reversal_target: Option<ReversalTarget>,
+    /// When the short drop off the seal ends
+    short_drop_deadline_millis: Option<u128>,
+    door_switch: DoorSwitchMonitor,
+    window_switch: WindowSwitchMonitor,
+    /// Who asked for the last movement, kept after it has stopped
+    movement_source: Option<MovementSource>,
+    /// Last passenger lockout of the main server, kept while the link is lost and across reboots
+    passenger_lockout: PassengerLockoutState,
+    obstruction_detector: ObstructionDetector,
+    inrush_monitor: InrushMonitor,
+    thermal_model: MotorThermalModel,
+    ripple_counter: RippleCounter,
+    ripple_samples: Vec<Milliamps>,
+    calibration_run: Option<CalibrationRun>,
+    calibration: Option<PowerWindowCalibration>,
+    last_stop_cause: Option<StopCause>,
+    faults: FaultManager,
+    link: LinkMonitor,
+    last_reset: Option<ResetReport>,
+    /// Currents of the last successful read
+    last_currents: CurrentReport,
+    /// Since when the running motor has drawn less than the minimum running current
+    missing_current_since_millis: Option<u128>,
+    status_sender: watch::Sender<PowerWindowStatus>,
+    snapshot_sender: watch::Sender<Option<DoorSnapshot>>,
+    telemetry_sender: broadcast::Sender<TelemetryEvent>,
+    /// Raw value of the state last sent as telemetry
+    telemetry_state: Option<u8>,
 }
 
-impl PowerWindowSvc {
+impl<TOutput, TInput, TClock, TStore> PowerWindowSvc<TOutput, TInput, TClock, TStore>
+where
+    TOutput: RelayOutputs + 'static,
+    TInput: CurrentSenseInputs + 'static,
+    TClock: Clock + 'static,
+    TStore: PersistentStore + 'static,
+{
+    /// Creates the service, `config` is only used until a config or calibration has been persisted
     pub fn new(
-        pins: PowerWindowDriverPins,
+        window_driver: PowerWindowDriver<TOutput, TInput, TClock>,
+        store: TStore,
         config: PowerWindowsConfig,
-    ) -> Result<PowerWindowSvc, EspError> {
-        Ok(PowerWindowSvc {
-            window_driver: PowerWindowDriver::new(pins)?,
+    ) -> PowerWindowSvc<TOutput, TInput, TClock, TStore> {
+        let config = config.clamped_to_sense_range();
+        let now_millis = window_driver.now_millis();
+
+        let mut svc = PowerWindowSvc {
+            window_driver,
+            store,
             last_handle_time_millis: 0,
             state: State::None,
-            config: config,
-        })
+            config,
+            default_config: config,
+            position: WindowPositionEstimator::new(WindowTravelConfig::default()),
+            target_position: None,
+            reversal_target: None,
+            short_drop_deadline_millis: None,
+            door_switch: DoorSwitchMonitor::default(),
+            window_switch: WindowSwitchMonitor::default(),
+            movement_source: None,
+            passenger_lockout: PassengerLockoutState::default(),
+            obstruction_detector: ObstructionDetector::new(ObstructionDetectorConfig::default()),
+            inrush_monitor: InrushMonitor::default(),
+            thermal_model: MotorThermalModel::default(),
+            ripple_counter: RippleCounter::default(),
+            ripple_samples: Vec::new(),
+            calibration_run: None,
+            calibration: None,
+            last_stop_cause: None,
+            faults: FaultManager::default(),
+            link: LinkMonitor::new(now_millis),
+            last_reset: None,
+            last_currents: CurrentReport::default(),
+            missing_current_since_millis: None,
+            status_sender: watch::channel(PowerWindowStatus::default()).0,
+            snapshot_sender: watch::channel(None).0,
+            telemetry_sender: broadcast::channel(TELEMETRY_CHANNEL_SIZE).0,
+            telemetry_state: None,
+        };
+
+        svc.load_settings();
+        svc.apply_current_sense_config();
+        svc
     }
 
-    pub async fn run_loop(
-        mut receiver: broadcast::Receiver<ServerRequest>,
-        svc: Arc<Mutex<PowerWindowSvc>>,
-    ) {
-        log::info!("Spawned power window service.");
+    pub fn state(&self) -> State {
+        self.state
+    }
 
-        let svc_src = svc.clone();
+    /// Receives a fresh status whenever the service has handled a request or polled the motor
+    pub fn subscribe_status(&self) -> watch::Receiver<PowerWindowStatus> {
+        self.status_sender.subscribe()
+    }
 
-        const ERROR_THRESHOLD: u8 = 3;
-        let error_count = Arc::new(Mutex::<u8>::new(0));
+    /// Receives what the status report and the fault list are built from whenever the status
+    /// is published, there is nothing before
+    pub fn subscribe_snapshot(&self) -> watch::Receiver<Option<DoorSnapshot>> {
+        self.snapshot_sender.subscribe()
+    }
 
-        async fn handle_error(error_count: Arc<Mutex<u8>>) {
-            let mut error_count = error_count.lock().await;
-            *error_count += 1;
+    /// Receives state changes as they are published and every current reading of the control loop
+    pub fn subscribe_telemetry(&self) -> broadcast::Receiver<TelemetryEvent> {
+        self.telemetry_sender.subscribe()
+    }
 
-            if *error_count > ERROR_THRESHOLD {
-                panic!("Too many errors, shutting down!");
-            }
+    /// Whether a relay is energised
+    pub fn is_motor_running(&self) -> bool {
+        !matches!(self.window_driver.state, WindowDriverState::INTERRUPTED)
+    }
+
+    /// Takes note of the reset the system started from, keeping a fault if it interrupted a movement
+    pub fn report_reset(&mut self, report: ResetReport) {
+        match report.record {
+            Some(record) => log::info!(
+                "Started after {:?} reset, last state was {} with the motor {}.",
+                report.reason,
+                record.state,
+                if record.motor_running { "running" } else { "stopped" }
+            ),
+            None => log::info!("Started after {:?} reset.", report.reason),
         }
 
-        async fn handle_result(result: Result<(), EspError>, error_count: Arc<Mutex<u8>>) {
-            match result {
-                Ok(_) => {}
-                Err(err) => {
-                    log::error!("Error: {:?}", err);
-                    handle_error(error_count.clone()).await;
-                }
-            }
+        if report.is_unexpected_during_motion() {
+            log::error!("Unexpected reset while the motor was running!");
+
+            // The reset is over, its fault is kept until it's cleared
+            let now_millis = self.window_driver.now_millis();
+            self.faults.raise(FaultCode::UnexpectedReset, now_millis);
+            self.faults.resolve(FaultCode::UnexpectedReset, now_millis);
         }
 
-        let svc = svc_src.clone();
-        let server_listener_task = tokio::spawn(async move {
-            loop {
-                let server_request = match receiver.recv().await {
-                    Ok(event) => event,
-                    Err(err) => match err {
-                        broadcast::error::RecvError::Closed => panic!("Event channel closed!"),
-                        broadcast::error::RecvError::Lagged(count) => {
+        self.last_reset = Some(report);
+    }
+
+    pub fn set_travel_config(&mut self, travel_config: WindowTravelConfig) {
+        self.position.set_travel_config(travel_config, self.window_driver.now_millis());
+    }
+
+    /// Runs the service until the request channel closes, `watchdog` is fed and `door_switch`
+    /// and `window_switch` are read on every control loop tick.
+    ///
+    /// Requests, current trips and the control loop ticks are handled one after another by this
+    /// task alone, nothing else touches the service. A current trip is handled as soon as the
+    /// sampling has seen it, instead of waiting for the next tick.
+    pub async fn run_loop(
+        mut receiver: broadcast::Receiver<ServerRequest>,
+        mut svc: PowerWindowSvc<TOutput, TInput, TClock, TStore>,
+        mut watchdog: impl LoopWatchdog + 'static,
+        mut door_switch: impl DoorSwitchInput + 'static,
+        mut window_switch: impl WindowSwitchInput + 'static,
+    ) {
+        log::info!("Spawned power window service.");
+
+        let current_trip = svc.window_driver.current_trip();
+        let mut interval = tokio::time::interval(CONTROL_INTERVAL);
+
+        loop {
+            tokio::select! {
+                request = receiver.recv() => {
+                    let server_request = match request {
+                        Ok(event) => event,
+                        Err(broadcast::error::RecvError::Closed) => panic!("Event channel closed!"),
+                        Err(broadcast::error::RecvError::Lagged(count)) => {
                             log::warn!("Event channel lagged by {} events, skipping...", count);
                             continue;
                         }
-                    },
-                };
+                    };
+
+                    let result = svc.handle_request(&server_request);
+                    svc.handle_result(result);
+                }
+                _ = current_trip.notified() => {
+                    log::debug!("Current trip, checking the motor current right away...");
 
-                match server_request.request_type {
-                    ServerRequestType::Open => {
-                        let mut svc = svc.lock().await;
-                        handle_result(svc.handle_opening(true), error_count.clone()).await;
-                    }
-                    ServerRequestType::Close => {
-                        let mut svc = svc.lock().await;
-                        handle_result(svc.handle_closing(true), error_count.clone()).await;
-                    }
-                    ServerRequestType::OpenFully => {
-                        let mut svc = svc.lock().await;
-                        handle_result(svc.handle_opening(false), error_count.clone()).await;
-                    }
-                    ServerRequestType::CloseFully => {
-                        let mut svc = svc.lock().await;
-                        handle_result(svc.handle_closing(false), error_count.clone()).await;
-                    }
-                    ServerRequestType::Stop => {
-                        let mut svc = svc.lock().await;
-                        handle_result(svc.handle_stop(), error_count.clone()).await;
-                    }
-                    ServerRequestType::ConfigureCurrentThresholds => {
-                        let mut svc = svc.lock().await;
-                        handle_result(svc.configure(server_request.request_data), error_count.clone()).await;
-                    },
+                    let result = svc.handle_current_interrupts();
+                    svc.handle_result(result);
                 }
+                _ = interval.tick() => svc.handle_tick(&mut watchdog, &mut door_switch, &mut window_switch),
             }
-        });
 
-        let svc = svc_src.clone();
-        let interrupt_handler_task = tokio::spawn(async move {
-            let mut interval = tokio::time::interval(tokio::time::Duration::from_millis(50));
+            svc.arm_current_trip();
+            svc.publish_status();
+        }
+    }
 
-            let mut _svc = svc.lock().await;
+    /// Runs a single control loop tick
+    fn handle_tick(
+        &mut self,
+        watchdog: &mut impl LoopWatchdog,
+        door_switch: &mut impl DoorSwitchInput,
+        window_switch: &mut impl WindowSwitchInput,
+    ) {
+        self.update_faults();
 
-            let handle_time_threshold: Duration = Duration::from_millis(_svc.config.handle_time_threshold_millis.into());
+        watchdog.feed(ResetRecord {
+            state: self.state as u8,
+            motor_running: self.is_motor_running(),
+        });
 
-            drop(_svc);
+        let result = self.handle_link_loss();
+        self.handle_result(result);
+        let result = door_switch.is_door_open().and_then(|door_open| self.handle_door_switch(door_open));
+        self.handle_result(result);
+        let result = window_switch
+            .read_switch_state()
+            .and_then(|switch_state| self.handle_window_switch(switch_state));
+        self.handle_result(result);
 
-            loop {
-                interval.tick().await;
+        match self.handle_continuous_timeout() {
+            Ok(true) => return,
+            Ok(false) => {}
+            Err(err) => self.handle_result(Err(err)),
+        }
 
-                let mut svc = svc.lock().await;
+        let result = self.handle_ripple_counting();
+        self.handle_result(result);
+        let result = self.handle_position_target();
+        self.handle_result(result);
+        let result = self.handle_reversal();
+        self.handle_result(result);
+        let result = self.handle_short_drop();
+        self.handle_result(result);
 
-                if get_time_as_millis() - svc.last_handle_time_millis >= handle_time_threshold.as_millis() {
-                    match svc.state {
-                        State::ClosingContinuous => {
-                            log::info!("Closing continuous timeout, stopping...");
-                            svc.handle_stop().unwrap();
-                            continue;
-                        }
-                        State::OpeningContinuous => {
-                            log::info!("Opening continuous timeout, stopping...");
-                            svc.handle_stop().unwrap();
-                            continue;
-                        }
-                        _ => {}
-                    }
+        // There are no end stops on the bench, full movements are ended after a while instead
+        if crate::DEBUG && self.window_driver.now_millis() - self.last_handle_time_millis >= 4000 {
+            let result = match self.state {
+                State::ClosingFully => {
+                    log::info!("DEBUG MODE: Closed fully, stopping...");
+                    self.handle_interrupt(TravelDirection::Closing, StopCause::EndOfTravel)
+                }
+                State::OpeningFully => {
+                    log::info!("DEBUG MODE: Opened fully, stopping...");
+                    self.handle_interrupt(TravelDirection::Opening, StopCause::EndOfTravel)
                 }
+                _ => Ok(()),
+            };
+            self.handle_result(result);
+        }
 
-                if crate::DEBUG {
-                    let current_state = svc.window_driver.read_current().unwrap();
-                    log::info!(
-                        "Current: closing_{}mA, opening_{}mA",
-                        current_state.closing_current,
-                        current_state.opening_current
-                    );
-
-                    if get_time_as_millis() - svc.last_handle_time_millis >= 4000 {
-                        match svc.state {
-                            State::ClosingFully => {
-                                log::info!("DEBUG MODE: Closed fully, stopping...");
-                                svc.handle_close_interrupt().unwrap();
-                                continue;
-                            }
-                            State::OpeningFully => {
-                                log::info!("DEBUG MODE: Opened fully, stopping...");
-                                svc.handle_open_interrupt().unwrap();
-                                continue;
-                            }
-                            _ => {}
-                        }
-                    }
+        let result = self.handle_current_interrupts();
+        self.handle_result(result);
+    }
 
-                    tokio::time::sleep(Duration::from_millis(500)).await;
-                    continue;
-                }
+    /// Arms the sampled current comparison at the limit the control loop stops the motor at,
+    /// so exceeding it wakes the loop right away. Nothing is armed while the motor is stopped.
+    pub fn arm_current_trip(&mut self) {
+        let now_millis = self.window_driver.now_millis();
+        let blanking = self.inrush_monitor.is_blanking(self.config.inrush_blanking_time, now_millis);
 
-                let current_state = svc.window_driver.read_current().unwrap();
-                log::debug!(
-                    "Current: closing_{}mA, opening_{}mA",
-                    current_state.closing_current,
-                    current_state.opening_current
-                );
+        let limit = match self.window_driver.state {
+            WindowDriverState::INTERRUPTED => None,
+            _ if blanking || matches!(self.state, State::Calibrating) => Some(self.config.inrush_current_limit),
+            WindowDriverState::CLOSING => Some(self.config.closing_current_interrupt_threshold),
+            WindowDriverState::OPENING => Some(self.config.opening_current_interrupt_threshold),
+        };
 
-                if current_state.closing_current
-                    > svc.config.closing_current_interrupt_threshold_amps
-                {
-                    svc.handle_close_interrupt().unwrap();
-                }
+        self.window_driver.arm_current_trip(limit);
+    }
 
-                if current_state.opening_current
-                    > svc.config.opening_current_interrupt_threshold_amps
-                {
-                    svc.handle_open_interrupt().unwrap();
-                }
-            }
+    /// Applies a single request, independent of where it came from
+    pub fn handle_request(&mut self, request: &ServerRequest) -> Result<(), HalError> {
+        self.update_faults();
+
+        if self.faults.is_blocking() && Self::is_motion_request(&request.request_type) {
+            log::warn!(
+                "Fault {:?} present, ignoring {:?}...",
+                self.faults.most_severe().map(|entry| entry.code),
+                request.request_type
+            );
+            return Ok(());
+        }
+
+        if self.thermal_model.is_locked_out() && Self::is_motion_request(&request.request_type) {
+            log::warn!(
+                "Motor too hot, ignoring {:?} for another {}s...",
+                request.request_type,
+                self.thermal_model.cooldown_secs(self.thermal_limits())
+            );
+            return Ok(());
+        }
+
+        // Whatever was queued while the link was down may be long outdated
+        if self.link.is_lost() && Self::is_motion_request(&request.request_type) {
+            log::warn!("Link to the main server lost, ignoring {:?} until the next heartbeat...", request.request_type);
+            return Ok(());
+        }
+
+        match request.request_type {
+            ServerRequestType::Open => self.handle_opening(true, MovementSource::MainServer),
+            ServerRequestType::Close => self.handle_closing(true, MovementSource::MainServer),
+            ServerRequestType::OpenFully => self.handle_opening(false, MovementSource::MainServer),
+            ServerRequestType::CloseFully => self.handle_closing(false, MovementSource::MainServer),
+            ServerRequestType::Stop => self.handle_server_stop(),
+            ServerRequestType::ConfigureCurrentThresholds => self.configure(request.request_data),
+            ServerRequestType::ResetConfig => self.reset_config(),
+            ServerRequestType::ClearFault => self.clear_fault(request.request_data),
+            ServerRequestType::MoveToPosition => self.handle_move_to_position(request.request_data),
+            ServerRequestType::Calibrate => self.handle_calibrate(),
+            ServerRequestType::Heartbeat => self.handle_heartbeat(request.request_data),
+        }
+    }
+
+    pub fn publish_status(&mut self) {
+        let inrush = self.inrush_monitor.record();
+        let thermal_limits = self.thermal_limits();
+
+        self.status_sender.send_replace(PowerWindowStatus {
+            state: self.state as u8,
+            position_percent: self.position.estimate_percent(self.window_driver.now_millis()),
+            last_stop_cause: self.last_stop_cause.map(|cause| cause as u8),
+            fault: self.faults.most_severe().map(|entry| entry.code as u8),
+            thermal_load_percent: self.thermal_model.load_percent(thermal_limits),
+            cooldown_secs: self.thermal_model.cooldown_secs(thermal_limits),
+            reset_reason: self.last_reset.map_or(0, |report| report.reason as u8),
+            state_before_reset: self.last_reset.and_then(|report| report.record).map(|record| record.state),
+            inrush_peak_current: inrush.peak_current,
+            inrush_duration: inrush.duration,
+            movement_source: self.movement_source.map(|source| source as u8),
+            passenger_lockout: self.passenger_lockout.locked,
         });
 
-        let (server_listener_task_result, interrupt_handler_task_result) =
-            join!(server_listener_task, interrupt_handler_task);
-        server_listener_task_result.unwrap();
-        interrupt_handler_task_result.unwrap();
+        self.snapshot_sender.send_replace(Some(self.snapshot()));
+
+        if self.telemetry_state != Some(self.state as u8) {
+            self.telemetry_state = Some(self.state as u8);
+
+            let now_millis = self.window_driver.now_millis();
+            self.send_telemetry(TelemetryEvent::State {
+                uptime_millis: now_millis as u64,
+                state: format!("{:?}", self.state),
+                state_code: self.state as u8,
+                position_percent: self.position.estimate_percent(now_millis),
+            });
+        }
     }
 
-    fn handle_opening(&mut self, continuous: bool) -> Result<(), EspError> {
-        self.last_handle_time_millis = get_time_as_millis();
+    fn send_telemetry(&self, event: TelemetryEvent) {
+        // Nobody listening is fine, the events are only for watching
+        if self.telemetry_sender.receiver_count() > 0 {
+            let _ = self.telemetry_sender.send(event);
+        }
+    }
 
-        match self.state {
-            State::OpeningContinuous => {
-                if continuous {
-                    log::debug!("Already opening, timeout slid forward.");
-                    return Ok(());
-                } else {
-                    log::info!(
-                        "Already opening, but upgrading from OPENING_CONTINUOUS to OPENING_FULLY."
-                    );
+    /// Copies what the status report and the fault list are built from
+    pub fn snapshot(&self) -> DoorSnapshot {
+        let now_millis = self.window_driver.now_millis();
+        let mut faults = [None; FaultCode::ALL.len()];
 
-                    self.state = State::OpeningFully;
-                    return Ok(());
-                }
+        for (slot, entry) in faults.iter_mut().zip(self.faults.entries()) {
+            *slot = Some(*entry);
+        }
+
+        DoorSnapshot {
+            uptime_millis: now_millis,
+            state: self.state,
+            position_percent: self.position.estimate_percent(now_millis),
+            currents: self.last_currents,
+            last_stop_cause: self.last_stop_cause,
+            config: self.config,
+            faults,
+            link_lost: self.link.is_lost(),
+            link_lost_millis: self.link.last_lost_millis(),
+            movement_source: self.movement_source,
+            passenger_lockout: self.passenger_lockout.locked,
+        }
+    }
+
+    /// Logs a failed step and brings the fault state up to date, the loops keep running whatever failed
+    pub fn handle_result(&mut self, result: Result<(), HalError>) {
+        if let Err(err) = result {
+            log::error!("Error: {:?}", err);
+        }
+
+        self.update_faults();
+    }
+
+    /// Takes over faults latched by the driver, drops faults which cleared themselves
+    /// and moves the window into or out of its safe state accordingly
+    pub fn update_faults(&mut self) {
+        let now_millis = self.window_driver.now_millis();
+
+        if let Some(fault) = self.window_driver.fault() {
+            self.raise_fault(fault.into());
+        }
+
+        self.faults.update(now_millis);
+
+        if matches!(self.state, State::Faulted) && !self.faults.is_blocking() {
+            log::info!("Faults gone, leaving the safe state.");
+
+            if let Err(err) = self.apply(Input::FaultsCleared) {
+                log::error!("Couldn't switch relays off: {:?}", err);
             }
-            State::OpeningFinished => {
-                log::info!("Tried opening more when already fully open, ignoring...");
-                return Ok(());
+        }
+    }
+
+    /// Records a fault, entering the safe state if it is critical
+    fn raise_fault(&mut self, code: FaultCode) {
+        self.faults.raise(code, self.window_driver.now_millis());
+
+        if code.severity() == Severity::Critical && !matches!(self.state, State::Faulted) {
+            self.enter_safe_state();
+        }
+    }
+
+    /// Cancels whatever the window was doing and switches both relays off
+    fn enter_safe_state(&mut self) {
+        log::error!("Critical fault present, entering the safe state...");
+
+        if let Err(err) = self.apply(Input::Fault) {
+            // The interlock has already tried switching both relays low, the driver latches the failure
+            log::error!("Couldn't switch relays off: {:?}", err);
+        }
+    }
+
+    /// Reads the filtered motor currents, a failing read raises the ADC fault instead of an error
+    fn read_current(&mut self) -> Option<WindowCurrentState> {
+        let now_millis = self.window_driver.now_millis();
+
+        match self.window_driver.read_current() {
+            Ok(current_state) => {
+                self.faults.resolve(FaultCode::AdcFailure, now_millis);
+                self.last_currents = CurrentReport {
+                    closing_milliamps: current_state.closing,
+                    opening_milliamps: current_state.opening,
+                    saturated: current_state.saturated,
+                };
+                self.send_telemetry(TelemetryEvent::Current {
+                    uptime_millis: now_millis as u64,
+                    closing_milliamps: current_state.closing,
+                    opening_milliamps: current_state.opening,
+                    saturated: current_state.saturated,
+                });
+                Some(current_state)
             }
-            State::OpeningFully => {
-                log::info!("Tried opening when open fully already running, ignoring...");
-                return Ok(());
+            Err(err) => {
+                log::error!("Couldn't read motor current: {:?}", err);
+                self.raise_fault(FaultCode::AdcFailure);
+                None
             }
-            State::OpeningInterrupted => {
-                log::info!("Tried opening when interrupted, ignoring...");
-                return Ok(());
+        }
+    }
+
+    /// Stops continuous movements which haven't been refreshed in time,
+    /// returns whether the window has been stopped
+    pub fn handle_continuous_timeout(&mut self) -> Result<bool, HalError> {
+        let threshold_millis = self.config.handle_time_threshold.as_millis();
+        if self.window_driver.now_millis() - self.last_handle_time_millis < threshold_millis {
+            return Ok(false);
+        }
+
+        self.apply(Input::ContinuousTimeout)
+    }
+
+    /// Counts the ripples in the motor current sampled since the last tick and
+    /// moves the estimated position by them
+    pub fn handle_ripple_counting(&mut self) -> Result<(), HalError> {
+        // Drained even when unused, so a movement never starts with stale samples
+        self.ripple_samples.clear();
+        if let Err(err) = self.window_driver.drain_current_samples(&mut self.ripple_samples) {
+            log::error!("Couldn't drain motor current samples: {:?}", err);
+            self.raise_fault(FaultCode::AdcFailure);
+            return Ok(());
+        }
+
+        let ripples_per_travel = self.config.ripples_per_travel;
+
+        if ripples_per_travel == 0 || matches!(self.window_driver.state, WindowDriverState::INTERRUPTED) {
+            return Ok(());
+        }
+
+        let now_millis = self.window_driver.now_millis();
+
+        // The inrush hides the ripple, the run time estimate covers the start
+        if self.inrush_monitor.is_blanking(self.config.inrush_blanking_time, now_millis) {
+            self.position.settle(now_millis);
+            return Ok(());
+        }
+
+        let ripples = self.ripple_counter.sample_burst(&self.ripple_samples);
+        self.position.track(ripples as f32 / ripples_per_travel as f32, now_millis);
+
+        Ok(())
+    }
+
+    /// Stops a movement to a position once the estimated position has reached its target
+    pub fn handle_position_target(&mut self) -> Result<(), HalError> {
+        let target = match (self.state, self.target_position) {
+            (State::OpeningToPosition | State::ClosingToPosition, Some(target)) => target,
+            _ => return Ok(()),
+        };
+
+        let position = match self.position.estimate(self.window_driver.now_millis()) {
+            Some(position) => position,
+            None => return Ok(()),
+        };
+
+        let reached = match self.state {
+            State::OpeningToPosition => position >= target - POSITION_TOLERANCE,
+            _ => position <= target + POSITION_TOLERANCE,
+        };
+
+        if reached {
+            log::info!("Reached target position of {}%.", (target * 100.0).round());
+            self.apply(Input::TargetReached)?;
+        }
+
+        Ok(())
+    }
+
+    /// Stops the reversal after a closing obstruction once it has driven far enough down
+    pub fn handle_reversal(&mut self) -> Result<(), HalError> {
+        let target = match (self.state, self.reversal_target) {
+            (State::ReversingAfterObstruction, Some(target)) => target,
+            _ => return Ok(()),
+        };
+
+        let now_millis = self.window_driver.now_millis();
+
+        let reversed = match target {
+            ReversalTarget::Position(target) => self
+                .position
+                .estimate(now_millis)
+                .is_some_and(|position| position >= target - POSITION_TOLERANCE),
+            ReversalTarget::Deadline(deadline_millis) => now_millis >= deadline_millis,
+        };
+
+        if reversed {
+            log::info!("Finished reversing after obstruction.");
+            self.apply(Input::ReversalFinished)?;
+        }
+
+        Ok(())
+    }
+
+    /// Feeds a reading of the door switch, dropping the glass off the seal once the door has opened
+    /// and raising it again once the door has shut
+    pub fn handle_door_switch(&mut self, door_open: bool) -> Result<(), HalError> {
+        match self.door_switch.update(door_open, self.window_driver.now_millis()) {
+            Some(true) => self.handle_door_opened(),
+            Some(false) => {
+                if self.apply(Input::DoorClosed)? {
+                    log::info!("Door shut, raising the glass again...");
+                    self.record_movement_source(MovementSource::DoorModule, TravelDirection::Closing);
+                }
+
+                Ok(())
             }
-            _ => {
-                log::info!("Starting opening...");
-                self.state = match continuous {
-                    true => State::OpeningContinuous,
-                    false => State::OpeningFully,
-                };
+            None => Ok(()),
+        }
+    }
+
+    fn handle_door_opened(&mut self) -> Result<(), HalError> {
+        let now_millis = self.window_driver.now_millis();
+
+        // Without a known position the glass may be at the seal, it's dropped to be safe
+        let at_seal = self.state.motion() == Some(TravelDirection::Closing)
+            || self
+                .position
+                .estimate(now_millis)
+                .map_or(true, |position| position < SEAL_CLEARANCE);
+
+        if !at_seal {
+            log::info!("Door opened, the glass is clear of the seal.");
+            return Ok(());
+        }
+
+        if self.apply(Input::DoorOpened)? {
+            log::info!("Door opened, dropping the glass off the seal...");
+            self.record_movement_source(MovementSource::DoorModule, TravelDirection::Opening);
+        }
+
+        Ok(())
+    }
+
+    /// Feeds a reading of the window switch on the door.
+    ///
+    /// The master switch wins whenever both switches want to move the window: the local switch
+    /// is ignored while the main server moves the window, while a command of the main server
+    /// takes over a movement of the local switch. Letting go of the local switch only stops
+    /// what it has started, and the stops of the idle master switch don't cut it short either.
+    /// The local switch keeps working while the link to the main server is lost, it is ignored
+    /// altogether while the main server has locked it out.
+    pub fn handle_window_switch(&mut self, switch_state: PowerWindowButtonState) -> Result<(), HalError> {
+        let switch_state = match self.window_switch.update(switch_state) {
+            Some(switch_state) => switch_state,
+            None => return Ok(()),
+        };
+
+        if self.passenger_lockout.locked {
+            log::debug!("Window switch locked out, ignoring local {:?}...", switch_state);
+            return Ok(());
+        }
+
+        if self.is_moved_by(MovementSource::MainServer) {
+            log::debug!("Window moved by the main server, ignoring local {:?}...", switch_state);
+            return Ok(());
+        }
+
+        if switch_state != PowerWindowButtonState::None
+            && (self.faults.is_blocking() || self.thermal_model.is_locked_out())
+        {
+            log::debug!("Motion blocked, ignoring local {:?}...", switch_state);
+            return Ok(());
+        }
+
+        match switch_state {
+            PowerWindowButtonState::None if self.is_moved_by(MovementSource::LocalSwitch) => self.handle_stop(),
+            PowerWindowButtonState::None => Ok(()),
+            PowerWindowButtonState::OpenContinuous => self.handle_opening(true, MovementSource::LocalSwitch),
+            PowerWindowButtonState::OpenFully => self.handle_opening(false, MovementSource::LocalSwitch),
+            PowerWindowButtonState::CloseContinuous => self.handle_closing(true, MovementSource::LocalSwitch),
+            PowerWindowButtonState::CloseFully => self.handle_closing(false, MovementSource::LocalSwitch),
+        }
+    }
+
+    /// Whether the window is moving on behalf of `source`
+    fn is_moved_by(&self, source: MovementSource) -> bool {
+        self.state.motion().is_some() && self.movement_source == Some(source)
+    }
+
+    /// Takes note of `source` asking for the movement, if the window is moving in `direction` now
+    fn record_movement_source(&mut self, source: MovementSource, direction: TravelDirection) {
+        if self.state.motion() == Some(direction) {
+            self.movement_source = Some(source);
+        }
+    }
+
+    /// Stops the short drop once the drop time has passed
+    pub fn handle_short_drop(&mut self) -> Result<(), HalError> {
+        let deadline_millis = match (self.state, self.short_drop_deadline_millis) {
+            (State::ShortDropping, Some(deadline_millis)) => deadline_millis,
+            _ => return Ok(()),
+        };
+
+        if self.window_driver.now_millis() >= deadline_millis {
+            log::info!("Glass dropped off the seal.");
+            self.apply(Input::ShortDropFinished)?;
+        }
+
+        Ok(())
+    }
+
+    /// Reads the motor current and interrupts the movement when the obstruction detector asks for it
+    pub fn handle_current_interrupts(&mut self) -> Result<(), HalError> {
+        let current_state = match self.read_current() {
+            Some(current_state) => current_state,
+            None => return Ok(()),
+        };
+        log::debug!(
+            "Current: closing_{}, opening_{}",
+            current_state.closing,
+            current_state.opening
+        );
+
+        if let Some(fault) = self.window_driver.supervise(&current_state) {
+            self.raise_fault(fault.into());
+            return Ok(());
+        }
+
+        let now_millis = self.window_driver.now_millis();
+        let position = self.position.estimate(now_millis);
 
-                self.window_driver.start_opening()?;
+        let thermal_limits = self.thermal_limits();
+        self.thermal_model.sample(
+            current_state.closing.saturating_add(current_state.opening),
+            thermal_limits,
+            now_millis,
+        );
+
+        if self.thermal_model.is_over_hard_limit(thermal_limits) {
+            self.handle_overheating()?;
+        }
+
+        let (direction, current, threshold) = match self.window_driver.state {
+            WindowDriverState::CLOSING => (
+                TravelDirection::Closing,
+                current_state.closing,
+                self.config.closing_current_interrupt_threshold,
+            ),
+            WindowDriverState::OPENING => (
+                TravelDirection::Opening,
+                current_state.opening,
+                self.config.opening_current_interrupt_threshold,
+            ),
+            WindowDriverState::INTERRUPTED => return Ok(()),
+        };
+
+        let blanking = self.inrush_monitor.is_blanking(self.config.inrush_blanking_time, now_millis);
+
+        // Past the inrush a saturated sensor means more current than it can measure
+        let current = match current_state.saturated && !blanking {
+            true => {
+                log::warn!("Current sense saturated at {}", current);
+                Milliamps::MAX
             }
+            false => current,
+        };
+
+        if blanking {
+            self.inrush_monitor.sample(current, threshold, now_millis);
+        } else if self.is_current_missing(current, now_millis) {
+            log::error!("Motor running without drawing current, the current sensing can't be trusted.");
+            self.raise_fault(FaultCode::ImplausibleCurrent);
+            return Ok(());
         }
 
-        return Ok(());
+        if matches!(self.state, State::Calibrating) {
+            return self.handle_calibration_sample(current, blanking, now_millis);
+        }
+
+        let cause = if blanking {
+            (current > self.config.inrush_current_limit)
+                .then(|| self.obstruction_detector.over_threshold_cause(direction, position))
+        } else {
+            self.obstruction_detector.sample(direction, position, current, threshold, now_millis)
+        };
+
+        match cause {
+            Some(cause) => self.handle_interrupt(direction, cause),
+            None => Ok(()),
+        }
     }
 
-    fn handle_closing(&mut self, continuous: bool) -> Result<(), EspError> {
-        self.last_handle_time_millis = get_time_as_millis();
+    /// Whether the energised relay has carried less than the minimum running current for too long
+    fn is_current_missing(&mut self, current: Milliamps, now_millis: u128) -> bool {
+        if current >= MIN_RUNNING_CURRENT {
+            self.missing_current_since_millis = None;
+            return false;
+        }
 
-        match self.state {
-            State::ClosingContinuous => {
-                if continuous {
-                    log::info!("Already closing, timeout slid forward.");
-                    return Ok(());
-                } else {
-                    log::info!(
-                        "Already closing, but upgrading from CLOSING_CONTINUOUS to CLOSING_FULLY."
-                    );
+        let since_millis = *self.missing_current_since_millis.get_or_insert(now_millis);
+
+        now_millis.saturating_sub(since_millis) >= MISSING_CURRENT_TIME.as_millis()
+    }
+
+    fn thermal_limits(&self) -> ThermalLimits {
+        ThermalLimits {
+            limit_amp2_seconds: self.config.thermal_limit_amp2_seconds as f32,
+            cooling_time_constant_secs: self.config.thermal_cooling_time_constant_secs as f32,
+        }
+    }
+
+    /// Stops a running movement to let the motor cool down, a reversal after an obstruction is let finish
+    fn handle_overheating(&mut self) -> Result<(), HalError> {
+        if self.apply(Input::Overheated)? {
+            log::error!("Motor overheated, stopped.");
+        }
+
+        Ok(())
+    }
+
+    /// Takes note of the main server being alive, accepting commands again if the link was lost,
+    /// and takes over the passenger lockout it carries
+    fn handle_heartbeat(&mut self, data: [u8; DTO_SIZE]) -> Result<(), HalError> {
+        let now_millis = self.window_driver.now_millis();
+
+        if self.link.heartbeat(now_millis) {
+            log::info!("Link to the main server is back.");
+            self.faults.resolve(FaultCode::LinkLost, now_millis);
+        }
+
+        self.set_passenger_lockout(PassengerLockoutState::deserialize(data))
+    }
+
+    /// Locks out the window switch on the door or lets it work again, persisting the change.
+    /// A movement of the window switch is stopped as if the switch had been let go.
+    fn set_passenger_lockout(&mut self, lockout: PassengerLockoutState) -> Result<(), HalError> {
+        if lockout == self.passenger_lockout {
+            return Ok(());
+        }
+
+        log::info!("Passenger lockout {}.", if lockout.locked { "engaged" } else { "released" });
+        self.passenger_lockout = lockout;
+
+        if lockout.locked && self.is_moved_by(MovementSource::LocalSwitch) {
+            self.handle_stop()?;
+        }
+
+        settings::store(&mut self.store, LOCKOUT_KEY, &self.passenger_lockout)
+    }
+
+    /// Enters link-loss mode once the main server's heartbeats have stopped, following the configured policy
+    pub fn handle_link_loss(&mut self) -> Result<(), HalError> {
+        let now_millis = self.window_driver.now_millis();
+
+        if !self.link.check(self.config.link_loss_timeout, now_millis) {
+            return Ok(());
+        }
+
+        log::error!(
+            "No heartbeat from the main server for {}, following the {:?} policy...",
+            self.config.link_loss_timeout,
+            self.config.link_loss_policy
+        );
+        self.faults.raise(FaultCode::LinkLost, now_millis);
+
+        let input = match self.config.link_loss_policy {
+            LinkLossPolicy::Stop => Input::LinkLost,
+            LinkLossPolicy::FinishMovement => Input::ContinuousTimeout,
+            // A hot motor isn't started, not even to close
+            LinkLossPolicy::CloseFully if self.thermal_model.is_locked_out() => Input::LinkLost,
+            LinkLossPolicy::CloseFully => Input::CloseFully,
+        };
+
+        if self.apply(input)? && input == Input::CloseFully {
+            self.record_movement_source(MovementSource::DoorModule, TravelDirection::Closing);
+        }
+
+        Ok(())
+    }
+
+    fn is_motion_request(request_type: &ServerRequestType) -> bool {
+        matches!(
+            request_type,
+            ServerRequestType::Open
+                | ServerRequestType::Close
+                | ServerRequestType::OpenFully
+                | ServerRequestType::CloseFully
+                | ServerRequestType::MoveToPosition
+                | ServerRequestType::Calibrate
+        )
+    }
 
-                    self.state = State::ClosingFully;
+    /// Clears the requested fault or all of them, leaving the safe state once no critical fault is left
+    fn clear_fault(&mut self, data: [u8; DTO_SIZE]) -> Result<(), HalError> {
+        let code = match ClearFaultRequest::deserialize(data).code {
+            None => None,
+            Some(raw) => match FaultCode::from_raw(raw) {
+                Some(code) => Some(code),
+                None => {
+                    log::warn!("Unknown fault code {}, ignoring clear request...", raw);
                     return Ok(());
                 }
+            },
+        };
+
+        let cleared = self.faults.clear(code);
+
+        if cleared.is_empty() {
+            log::info!("No fault {:?} present, ignoring clear request...", code);
+            return Ok(());
+        }
+
+        log::warn!("Clearing faults {:?}...", cleared);
+
+        if cleared.iter().any(|code| code.is_driver_fault()) {
+            self.window_driver.clear_fault();
+        }
+
+        if !matches!(self.state, State::Faulted) || self.faults.is_blocking() {
+            return Ok(());
+        }
+
+        // Switches both relays low again, leaving them in a known state
+        self.apply(Input::FaultsCleared).map(|_| ())
+    }
+
+    /// Looks up the transition for `input` and executes its actions,
+    /// returns whether the input has changed anything
+    fn apply(&mut self, input: Input) -> Result<bool, HalError> {
+        let previous = self.state;
+        let transition = transition(previous, input);
+
+        if transition.is_ignored(previous) {
+            log::debug!("{:?} ignored in {:?}.", input, previous);
+            return Ok(false);
+        }
+
+        if transition.next != previous {
+            log::info!("{:?}: {:?} -> {:?}", input, previous, transition.next);
+        }
+
+        self.state = transition.next;
+
+        for action in transition.actions {
+            self.execute(action, previous, input)?;
+        }
+
+        Ok(true)
+    }
+
+    fn execute(&mut self, action: Action, previous: State, input: Input) -> Result<(), HalError> {
+        let now_millis = self.window_driver.now_millis();
+
+        match action {
+            Action::StartOpening => self.start_opening()?,
+            Action::StartClosing => self.start_closing()?,
+            Action::StopMotor => self.stop_motor()?,
+            Action::StartReversal => self.start_reversal()?,
+            Action::ReferenceOpen => self.position.reference(1.0, now_millis),
+            Action::ReferenceClosed => self.position.reference(0.0, now_millis),
+            Action::RecordStopCause(cause) => self.last_stop_cause = Some(cause),
+            Action::ClearTarget => self.target_position = None,
+            Action::ClearReversal => self.reversal_target = None,
+            Action::StartCalibration => self.calibration_run = Some(CalibrationRun::new(now_millis)),
+            Action::CancelCalibration => self.calibration_run = None,
+            Action::StartDropTimer => {
+                self.short_drop_deadline_millis = Some(now_millis + self.config.short_drop_time.as_millis())
             }
-            State::ClosingFinished => {
-                log::info!("Tried closing more when already fully closed, ignoring...");
-                return Ok(());
-            }
-            State::ClosingFully => {
-                log::info!("Tried closing when closed fully already running, ignoring...");
-                return Ok(());
+            Action::ReportUnexpectedTransition => {
+                log::error!("{:?} unexpected in {:?}, stopped.", input, previous);
+
+                // The transition itself is over, only its report is kept for a while
+                self.faults.raise(FaultCode::UnexpectedTransition, now_millis);
+                self.faults.resolve(FaultCode::UnexpectedTransition, now_millis);
             }
-            State::ClosingInterrupted => {
-                log::info!("Tried closing when interrupted, ignoring...");
+        }
+
+        Ok(())
+    }
+
+    fn handle_opening(&mut self, continuous: bool, source: MovementSource) -> Result<(), HalError> {
+        self.last_handle_time_millis = self.window_driver.now_millis();
+
+        let input = match continuous {
+            true => Input::OpenContinuous,
+            false => Input::OpenFully,
+        };
+
+        if !self.apply(input)? {
+            log::info!("Tried opening while {:?}, ignoring...", self.state);
+        }
+
+        // Refreshing a movement takes it over as well
+        self.record_movement_source(source, TravelDirection::Opening);
+
+        Ok(())
+    }
+
+    fn handle_closing(&mut self, continuous: bool, source: MovementSource) -> Result<(), HalError> {
+        self.last_handle_time_millis = self.window_driver.now_millis();
+
+        let input = match continuous {
+            true => Input::CloseContinuous,
+            false => Input::CloseFully,
+        };
+
+        if !self.apply(input)? {
+            log::info!("Tried closing while {:?}, ignoring...", self.state);
+        }
+
+        // Refreshing a movement takes it over as well
+        self.record_movement_source(source, TravelDirection::Closing);
+
+        Ok(())
+    }
+
+    fn handle_move_to_position(&mut self, data: [u8; DTO_SIZE]) -> Result<(), HalError> {
+        let target_percent = MoveToPositionRequest::deserialize(data).position_percent.min(100);
+
+        // The end stops are found more precisely by current than by estimation
+        match target_percent {
+            0 => return self.handle_closing(false, MovementSource::MainServer),
+            100 => return self.handle_opening(false, MovementSource::MainServer),
+            _ => {}
+        }
+
+        let now_millis = self.window_driver.now_millis();
+        self.last_handle_time_millis = now_millis;
+
+        let position = match self.position.estimate(now_millis) {
+            Some(position) => position,
+            None => {
+                log::warn!(
+                    "Position unknown, open or close fully once before moving to {}%, ignoring...",
+                    target_percent
+                );
                 return Ok(());
             }
-            _ => {
-                log::info!("Starting closing...");
-                self.state = match continuous {
-                    true => State::ClosingContinuous,
-                    false => State::ClosingFully,
-                };
+        };
 
-                self.window_driver.start_closing()?;
-            }
+        let target = target_percent as f32 / 100.0;
+
+        if (target - position).abs() <= POSITION_TOLERANCE {
+            log::info!("Already at {}%, ignoring...", target_percent);
+            return Ok(());
         }
 
-        return Ok(());
+        let direction = match target > position {
+            true => TravelDirection::Opening,
+            false => TravelDirection::Closing,
+        };
+
+        if !self.apply(Input::MoveToPosition(direction))? {
+            log::info!("Tried moving to {}% while {:?}, ignoring...", target_percent, self.state);
+            return Ok(());
+        }
+
+        log::info!("Moving to {}%...", target_percent);
+        self.target_position = Some(target);
+        self.record_movement_source(MovementSource::MainServer, direction);
+
+        Ok(())
     }
 
-    fn handle_close_interrupt(&mut self)-> Result<(), EspError> {
-        self.window_driver.interrupt()?;
+    /// Stops the motor after the current detection has interrupted it while driving in `direction`
+    fn handle_interrupt(&mut self, direction: TravelDirection, cause: StopCause) -> Result<(), HalError> {
+        log::info!("Interrupted by {:?} while {:?} {:?}.", cause, self.state, direction);
 
-        match self.state {
-            State::ClosingContinuous => {
-                log::info!("Interrupted closing.");
-                self.state = State::ClosingInterrupted;
-            }
-            State::ClosingFully => {
-                log::debug!("Finished closing.");
-                self.state = State::ClosingFinished;
-            }
-            _ => {
-                log::error!("Interrupt requested but not closing!");
-                panic!("Interrupt requested but not closing!");
-            }
+        self.apply(Input::Interrupt(direction, cause)).map(|_| ())
+    }
+
+    /// Stops on behalf of the main server, whose idle master switch keeps sending stops.
+    /// Those don't cut short what the local switch is doing.
+    fn handle_server_stop(&mut self) -> Result<(), HalError> {
+        if self.is_moved_by(MovementSource::LocalSwitch) {
+            log::debug!("Window moved by the local switch, ignoring stop of the main server...");
+            return Ok(());
+        }
+
+        self.handle_stop()
+    }
+
+    fn handle_stop(&mut self) -> Result<(), HalError> {
+        if !self.apply(Input::Stop)? {
+            log::info!("{:?} runs to its end, therefore ignoring soft stop...", self.state);
         }
 
         Ok(())
     }
 
-    fn handle_open_interrupt(&mut self) -> Result<(), EspError> {
-        self.window_driver.interrupt()?;
+    fn configure(&mut self, data: [u8; DTO_SIZE]) -> Result<(), HalError> {
+        let pw_cfg = PowerWindowsConfig::deserialize(data);
 
-        match self.state {
-            State::OpeningContinuous => {
-                log::info!("Interrupted opening.");
-                self.state = State::OpeningInterrupted;
-            }
-            State::OpeningFully => {
-                log::debug!("Finished opening.");
-                self.state = State::OpeningFinished;
-            }
-            _ => {
-                log::error!("Interrupt requested but not opening!");
-                panic!("Interrupt requested but not opening!");
-            }
+        log::info!("Configuring power window service with:");
+        log::info!("Opening current interrupt threshold: {}", pw_cfg.opening_current_interrupt_threshold);
+        log::info!("Closing current interrupt threshold: {}", pw_cfg.closing_current_interrupt_threshold);
+        log::info!("Handle time threshold: {}", pw_cfg.handle_time_threshold);
+        log::info!("Obstruction reverse: {}% or {}", pw_cfg.obstruction_reverse_percent, pw_cfg.obstruction_reverse_time);
+        log::info!("Inrush blanking: {} up to {}", pw_cfg.inrush_blanking_time, pw_cfg.inrush_current_limit);
+        log::info!("Thermal limit: {}A²s, cooling in {}s", pw_cfg.thermal_limit_amp2_seconds, pw_cfg.thermal_cooling_time_constant_secs);
+        log::info!("Ripples per travel: {}", pw_cfg.ripples_per_travel);
+        log::info!("Link loss: {:?} after {}", pw_cfg.link_loss_policy, pw_cfg.link_loss_timeout);
+        log::info!("Short drop time: {}", pw_cfg.short_drop_time);
+        log::info!(
+            "Current sense: closing_{}, opening_{}, saturating at {}, zeroed after {}",
+            pw_cfg.closing_sense_gain,
+            pw_cfg.opening_sense_gain,
+            pw_cfg.sense_saturation,
+            pw_cfg.sense_zero_settle_time
+        );
+
+        self.set_config(pw_cfg);
+        self.apply_current_sense_config();
+
+        settings::store(&mut self.store, CONFIG_KEY, &self.config)
+    }
+
+    fn reset_config(&mut self) -> Result<(), HalError> {
+        log::info!("Resetting power window config to defaults...");
+
+        self.set_config(match self.calibration {
+            Some(calibration) => calibration.apply_to(self.default_config),
+            None => self.default_config,
+        });
+        self.apply_current_sense_config();
+
+        self.store.remove(CONFIG_KEY)
+    }
+
+    fn handle_calibrate(&mut self) -> Result<(), HalError> {
+        match self.apply(Input::Calibrate)? {
+            true => self.movement_source = Some(MovementSource::MainServer),
+            false => log::info!("Tried calibrating while {:?}, ignoring...", self.state),
         }
 
         Ok(())
     }
 
-    fn handle_stop(&mut self) -> Result<(), EspError> {
-        match self.state {
-            State::OpeningFully => {
-                log::info!("Opening fully, therefore ignoring soft stop...");
+    fn handle_calibration_sample(&mut self, current: Milliamps, blanking: bool, now_millis: u128) -> Result<(), HalError> {
+        if current > self.config.inrush_current_limit {
+            return self.fail_calibration("Current above the inrush limit");
+        }
+
+        if blanking {
+            return Ok(());
+        }
+
+        let step = match self.calibration_run.as_mut() {
+            Some(run) => run.sample(current, now_millis),
+            None => return Ok(()),
+        };
+
+        match step {
+            CalibrationStep::Continue => Ok(()),
+            CalibrationStep::NextPhase(direction) => {
+                log::info!("Calibration reached an end stop, continuing {:?}...", direction);
+                self.apply(Input::CalibrationPhase(direction)).map(|_| ())
+            }
+            CalibrationStep::Finished(calibration) => self.finish_calibration(calibration),
+            CalibrationStep::Failed(reason) => self.fail_calibration(reason),
+        }
+    }
+
+    fn finish_calibration(&mut self, calibration: PowerWindowCalibration) -> Result<(), HalError> {
+        self.apply(Input::CalibrationFinished)?;
+
+        log::info!("Calibration finished: {:?}", calibration);
+        self.apply_calibration(calibration);
+
+        settings::store(&mut self.store, CALIBRATION_KEY, &calibration)?;
+        settings::store(&mut self.store, CONFIG_KEY, &self.config)
+    }
+
+    fn fail_calibration(&mut self, reason: &'static str) -> Result<(), HalError> {
+        log::error!("Calibration failed: {}", reason);
+
+        self.apply(Input::CalibrationFailed).map(|_| ())
+    }
+
+    /// Restores the persisted calibration, config and passenger lockout, the persisted config wins
+    /// over calibrated thresholds
+    fn load_settings(&mut self) {
+        match settings::load::<_, PowerWindowCalibration>(&mut self.store, CALIBRATION_KEY) {
+            Ok(Some(calibration)) => {
+                log::info!("Loaded calibration: {:?}", calibration);
+                self.apply_calibration(calibration);
             }
-            State::ClosingFully => {
-                log::info!("Closing fully, therefore ignoring soft stop...");
+            Ok(None) => log::warn!("Window hasn't been calibrated yet, using the default thresholds."),
+            Err(err) => log::error!("Couldn't load calibration: {:?}", err),
+        }
+
+        match settings::load::<_, PowerWindowsConfig>(&mut self.store, CONFIG_KEY) {
+            Ok(Some(config)) => {
+                log::info!("Loaded config: {:?}", config);
+                self.set_config(config);
             }
-            _ => {
-                log::debug!("Stopping operation...");
-                self.state = State::Stopped;
-                self.window_driver.interrupt()?;
+            Ok(None) => log::info!("No persisted config, using defaults."),
+            Err(err) => log::error!("Couldn't load config: {:?}", err),
+        }
+
+        match settings::load::<_, PassengerLockoutState>(&mut self.store, LOCKOUT_KEY) {
+            Ok(Some(lockout)) => {
+                log::info!("Loaded passenger lockout: {:?}", lockout);
+                self.passenger_lockout = lockout;
             }
+            Ok(None) => {}
+            Err(err) => log::error!("Couldn't load passenger lockout: {:?}", err),
         }
+    }
 
-        return Ok(());
+    /// Takes over a config wherever it comes from, thresholds the current sense can't report would
+    /// never stop the motor and are lowered to what it can
+    fn set_config(&mut self, config: PowerWindowsConfig) {
+        self.config = config.clamped_to_sense_range();
     }
 
-    fn configure(&mut self, data: [u8; 8]) -> Result<(), EspError> {
-        let pw_cfg = PowerWindowsConfig::deserialize(data);
+    fn apply_current_sense_config(&mut self) {
+        self.window_driver.configure_current_sense(
+            CurrentSensorConfig {
+                gain: self.config.closing_sense_gain,
+                saturation: self.config.sense_saturation,
+            },
+            CurrentSensorConfig {
+                gain: self.config.opening_sense_gain,
+                saturation: self.config.sense_saturation,
+            },
+            self.config.sense_zero_settle_time,
+        );
+    }
 
-        log::info!("Configuring power window service with:");
-        log::info!("Opening current interrupt threshold: {}mA", pw_cfg.opening_current_interrupt_threshold_amps);
-        log::info!("Closing current interrupt threshold: {}mA", pw_cfg.closing_current_interrupt_threshold_amps);
-        log::info!("Handle time threshold: {}ms", pw_cfg.handle_time_threshold_millis);
+    /// Uses the measured travel times for position estimation and derives the interrupt thresholds
+    fn apply_calibration(&mut self, calibration: PowerWindowCalibration) {
+        if calibration.opening_travel_time == Milliseconds::ZERO || calibration.closing_travel_time == Milliseconds::ZERO {
+            log::warn!("Calibration without travel times, ignoring...");
+            return;
+        }
+
+        self.calibration = Some(calibration);
+        self.set_config(calibration.apply_to(self.config));
+        self.position.set_travel_config(
+            WindowTravelConfig {
+                opening_travel_millis: calibration.opening_travel_time.0 as u32,
+                closing_travel_millis: calibration.closing_travel_time.0 as u32,
+            },
+            self.window_driver.now_millis(),
+        );
+    }
+
+    /// Drives the glass back down after it has been stopped while closing, releasing whatever got trapped
+    fn start_reversal(&mut self) -> Result<(), HalError> {
+        let now_millis = self.window_driver.now_millis();
+
+        self.reversal_target = Some(match self.position.estimate(now_millis) {
+            Some(position) => ReversalTarget::Position(
+                (position + self.config.obstruction_reverse_percent as f32 / 100.0).min(1.0),
+            ),
+            None => ReversalTarget::Deadline(now_millis + self.config.obstruction_reverse_time.as_millis()),
+        });
+
+        log::info!("Reversing after obstruction...");
+        self.start_opening()
+    }
 
-        self.config = pw_cfg;
+    fn start_opening(&mut self) -> Result<(), HalError> {
+        self.missing_current_since_millis = None;
+        let relays_switched = !matches!(self.window_driver.state, WindowDriverState::OPENING);
+
+        self.window_driver.start_opening()?;
+        self.obstruction_detector.reset();
+
+        let now_millis = self.window_driver.now_millis();
+        if relays_switched {
+            self.inrush_monitor.start(now_millis);
+        }
+        self.position.start(TravelDirection::Opening, now_millis);
+        self.ripple_counter.reset();
 
         Ok(())
     }
-}
 
-fn get_time_as_millis() -> u128 {
-    return EspSystemTime::now(&EspSystemTime {}).as_millis();
+    fn start_closing(&mut self) -> Result<(), HalError> {
+        self.missing_current_since_millis = None;
+        let relays_switched = !matches!(self.window_driver.state, WindowDriverState::CLOSING);
+
+        self.window_driver.start_closing()?;
+        self.obstruction_detector.reset();
+
+        let now_millis = self.window_driver.now_millis();
+        if relays_switched {
+            self.inrush_monitor.start(now_millis);
+        }
+        self.position.start(TravelDirection::Closing, now_millis);
+        self.ripple_counter.reset();
+
+        Ok(())
+    }
+
+    fn stop_motor(&mut self) -> Result<(), HalError> {
+        self.missing_current_since_millis = None;
+        self.window_driver.interrupt()?;
+        self.position.stop(self.window_driver.now_millis());
+
+        let inrush = self.inrush_monitor.record();
+        log::debug!("Inrush of the last movement: {} peak, settled after {}", inrush.peak_current, inrush.duration);
+
+        Ok(())
+    }
 }
@@ -0,0 +1,70 @@
+use shared_lib::units::Milliamps;
+
+/// Weight of a new sample in the baseline which the ripple is measured against
+const BASELINE_WEIGHT: f32 = 0.05;
+/// Weight of a new sample in the average ripple amplitude
+const AMPLITUDE_WEIGHT: f32 = 0.02;
+/// Share of the average ripple amplitude the current has to swing past the baseline
+const HYSTERESIS_FACTOR: f32 = 0.5;
+/// Swings smaller than this are treated as noise
+const MIN_HYSTERESIS: Milliamps = Milliamps(20);
+
+/// Counts the current ripple a brushed motor produces per commutator segment.
+///
+/// The current is high-pass filtered against a slowly following baseline and
+/// passed through a Schmitt trigger whose hysteresis adapts to the ripple
+/// amplitude, every upward crossing counts as one ripple. Samples have to be
+/// evenly spaced and taken at a multiple of the ripple frequency.
+#[derive(Default)]
+pub struct RippleCounter {
+    baseline: Option<f32>,
+    /// Average absolute deviation from the baseline
+    amplitude: f32,
+    above: bool,
+    ripples: u32,
+}
+
+impl RippleCounter {
+    /// Forgets the previous movement, the motor current has to settle again
+    pub fn reset(&mut self) {
+        *self = RippleCounter::default();
+    }
+
+    /// Ripples counted since the last reset
+    pub fn ripples(&self) -> u32 {
+        self.ripples
+    }
+
+    /// Feeds a single current sample, returns whether it completed a ripple
+    pub fn sample(&mut self, current: Milliamps) -> bool {
+        let current = current.0 as f32;
+
+        let baseline = match self.baseline {
+            Some(baseline) => baseline + (current - baseline) * BASELINE_WEIGHT,
+            None => current,
+        };
+        self.baseline = Some(baseline);
+
+        let deviation = current - baseline;
+        self.amplitude += (deviation.abs() - self.amplitude) * AMPLITUDE_WEIGHT;
+
+        let hysteresis = (self.amplitude * HYSTERESIS_FACTOR).max(MIN_HYSTERESIS.0 as f32);
+
+        if !self.above && deviation > hysteresis {
+            self.above = true;
+            self.ripples += 1;
+            return true;
+        }
+
+        if self.above && deviation < -hysteresis {
+            self.above = false;
+        }
+
+        false
+    }
+
+    /// Feeds evenly spaced samples, returns the amount of ripples they completed
+    pub fn sample_burst(&mut self, samples: &[Milliamps]) -> u32 {
+        samples.iter().filter(|sample| self.sample(**sample)).count() as u32
+    }
+}
@@ -0,0 +1,68 @@
+use shared_lib::dto::pw_config::{Deserialize, Serialize, DTO_SIZE};
+
+use crate::hal::traits::{HalError, PersistentStore};
+
+/// Layout version of persisted records, bump whenever a persisted DTO changes incompatibly
+const SETTINGS_VERSION: u8 = 1;
+/// Version byte and CRC in front of the DTO
+const HEADER_SIZE: usize = 3;
+const RECORD_SIZE: usize = HEADER_SIZE + DTO_SIZE;
+
+pub const CONFIG_KEY: &str = "pw_config";
+pub const CALIBRATION_KEY: &str = "pw_calibration";
+pub const LOCKOUT_KEY: &str = "pw_lockout";
+
+/// Reads a DTO persisted with `store`, records of another version or with
+/// a checksum mismatch are treated as missing
+pub fn load<TStore, TDto>(store: &mut TStore, key: &str) -> Result<Option<TDto>, HalError>
+where
+    TStore: PersistentStore,
+    TDto: Deserialize,
+{
+    let mut record: [u8; RECORD_SIZE] = [0; RECORD_SIZE];
+
+    if !store.load(key, &mut record)? {
+        return Ok(None);
+    }
+
+    if record[0] != SETTINGS_VERSION {
+        log::warn!("Stored {} has version {} instead of {}, ignoring...", key, record[0], SETTINGS_VERSION);
+        return Ok(None);
+    }
+
+    let mut dto: [u8; DTO_SIZE] = [0; DTO_SIZE];
+    dto.copy_from_slice(&record[HEADER_SIZE..]);
+
+    if u16::from_be_bytes([record[1], record[2]]) != crc16(&dto) {
+        log::warn!("Stored {} is corrupted, ignoring...", key);
+        return Ok(None);
+    }
+
+    Ok(Some(TDto::deserialize(dto)))
+}
+
+/// Persists a DTO together with the settings version and a checksum
+pub fn store<TStore, TDto>(store: &mut TStore, key: &str, dto: &TDto) -> Result<(), HalError>
+where
+    TStore: PersistentStore,
+    TDto: Serialize,
+{
+    let dto = dto.serialize();
+
+    let mut record: [u8; RECORD_SIZE] = [0; RECORD_SIZE];
+    record[0] = SETTINGS_VERSION;
+    record[1..HEADER_SIZE].copy_from_slice(&crc16(&dto).to_be_bytes());
+    record[HEADER_SIZE..].copy_from_slice(&dto);
+
+    store.store(key, &record)
+}
+
+/// CRC-16/CCITT-FALSE
+fn crc16(data: &[u8]) -> u16 {
+    data.iter().fold(0xFFFF, |crc, byte| {
+        (0..8).fold(crc ^ ((*byte as u16) << 8), |crc, _| match crc & 0x8000 {
+            0 => crc << 1,
+            _ => (crc << 1) ^ 0x1021,
+        })
+    })
+}
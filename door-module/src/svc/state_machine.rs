@@ -0,0 +1,636 @@
+use super::{obstruction_detector::StopCause, window_position::TravelDirection};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum State {
+    None = 0,
+    Stopped = 0b0001,
+
+    OpeningContinuous = 0b0010,
+    OpeningFully = 0b0110,
+    OpeningInterrupted = 0b0011,
+    OpeningFinished = 0b0111,
+    OpeningObstructed = 0b100011,
+    OpeningStalled = 0b1000011,
+
+    ClosingContinuous = 0b1010,
+    ClosingFully = 0b1110,
+    ClosingInterrupted = 0b1011,
+    ClosingFinished = 0b1111,
+
+    OpeningToPosition = 0b10010,
+    ClosingToPosition = 0b11010,
+    PositionReached = 0b10001,
+
+    ReversingAfterObstruction = 0b100010,
+    ReversedAfterObstruction = 0b100001,
+
+    Calibrating = 0b10000010,
+    CalibrationFinished = 0b10000111,
+    CalibrationFailed = 0b10000011,
+
+    /// Dropping the frameless glass off the seal while the door is open
+    ShortDropping = 0b1000010,
+    /// The glass is clear of the seal, it rises again once the door shuts
+    ShortDropped = 0b1000001,
+
+    /// A critical fault is present, both relays are off and motion is refused
+    Faulted = 0b11111111,
+}
+
+impl State {
+    pub const ALL: [State; 23] = [
+        State::None,
+        State::Stopped,
+        State::OpeningContinuous,
+        State::OpeningFully,
+        State::OpeningInterrupted,
+        State::OpeningFinished,
+        State::OpeningObstructed,
+        State::OpeningStalled,
+        State::ClosingContinuous,
+        State::ClosingFully,
+        State::ClosingInterrupted,
+        State::ClosingFinished,
+        State::OpeningToPosition,
+        State::ClosingToPosition,
+        State::PositionReached,
+        State::ReversingAfterObstruction,
+        State::ReversedAfterObstruction,
+        State::Calibrating,
+        State::CalibrationFinished,
+        State::CalibrationFailed,
+        State::ShortDropping,
+        State::ShortDropped,
+        State::Faulted,
+    ];
+
+    /// Direction the motor runs in while in this state, `None` if it's stopped.
+    /// The calibration drives both ways, it reports `None` as well.
+    pub fn motion(&self) -> Option<TravelDirection> {
+        match self {
+            State::OpeningContinuous
+            | State::OpeningFully
+            | State::OpeningToPosition
+            | State::ReversingAfterObstruction
+            | State::ShortDropping => Some(TravelDirection::Opening),
+            State::ClosingContinuous | State::ClosingFully | State::ClosingToPosition => Some(TravelDirection::Closing),
+            _ => None,
+        }
+    }
+}
+
+/// Everything which can make the window change its state
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Input {
+    OpenContinuous,
+    OpenFully,
+    CloseContinuous,
+    CloseFully,
+    /// Move to a target position which lies in the given direction
+    MoveToPosition(TravelDirection),
+    Stop,
+    Calibrate,
+
+    /// A continuous movement hasn't been refreshed in time
+    ContinuousTimeout,
+    /// The motor has been interrupted while driving in the given direction
+    Interrupt(TravelDirection, StopCause),
+    /// The estimated position has reached the target position
+    TargetReached,
+    /// The reversal after an obstruction has driven far enough
+    ReversalFinished,
+    /// The motor has to cool down right away
+    Overheated,
+    /// The main server's heartbeats have stopped, every movement has to stop
+    LinkLost,
+
+    /// The calibration has reached an end stop and continues in the given direction
+    CalibrationPhase(TravelDirection),
+    CalibrationFinished,
+    CalibrationFailed,
+
+    /// The door has been opened with the glass at the seal
+    DoorOpened,
+    /// The door has been shut
+    DoorClosed,
+    /// The glass has dropped for the configured drop time
+    ShortDropFinished,
+
+    /// A critical fault has been raised
+    Fault,
+    /// No critical fault is left
+    FaultsCleared,
+}
+
+impl Input {
+    /// Every possible input, including every interrupt cause in both directions
+    pub fn all() -> Vec<Input> {
+        let directions = [TravelDirection::Opening, TravelDirection::Closing];
+
+        let mut inputs = vec![
+            Input::OpenContinuous,
+            Input::OpenFully,
+            Input::CloseContinuous,
+            Input::CloseFully,
+            Input::Stop,
+            Input::Calibrate,
+            Input::ContinuousTimeout,
+            Input::TargetReached,
+            Input::ReversalFinished,
+            Input::Overheated,
+            Input::LinkLost,
+            Input::CalibrationFinished,
+            Input::CalibrationFailed,
+            Input::DoorOpened,
+            Input::DoorClosed,
+            Input::ShortDropFinished,
+            Input::Fault,
+            Input::FaultsCleared,
+        ];
+
+        for direction in directions {
+            inputs.push(Input::MoveToPosition(direction));
+            inputs.push(Input::CalibrationPhase(direction));
+            inputs.extend(StopCause::ALL.map(|cause| Input::Interrupt(direction, cause)));
+        }
+
+        inputs
+    }
+}
+
+/// Side effects of a transition, executed in order by the service
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Action {
+    StartOpening,
+    StartClosing,
+    /// Switches both relays off
+    StopMotor,
+    /// Opens by the configured reversal distance after a closing obstruction
+    StartReversal,
+    /// The glass is at the upper end stop
+    ReferenceOpen,
+    /// The glass is at the lower end stop
+    ReferenceClosed,
+    RecordStopCause(StopCause),
+    ClearTarget,
+    ClearReversal,
+    StartCalibration,
+    CancelCalibration,
+    /// Starts timing the short drop, it ends after the configured drop time
+    StartDropTimer,
+    /// The input isn't expected in the current state and is kept as a fault
+    ReportUnexpectedTransition,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Transition {
+    pub next: State,
+    pub actions: Vec<Action>,
+}
+
+impl Transition {
+    /// Whether the input has been ignored, leaving the state and the motor as they are
+    pub fn is_ignored(&self, state: State) -> bool {
+        self.next == state && self.actions.is_empty()
+    }
+}
+
+/// Where a rule leads to
+#[derive(Debug, Clone, Copy)]
+enum Next {
+    Stay,
+    To(State),
+}
+
+struct Rule {
+    from: &'static [State],
+    on: &'static [Input],
+    next: Next,
+    actions: &'static [Action],
+}
+
+const ANY: &[State] = &State::ALL;
+const OPENING_MOVES: &[State] = &[State::OpeningContinuous, State::OpeningFully, State::OpeningToPosition];
+const CLOSING_MOVES: &[State] = &[State::ClosingContinuous, State::ClosingFully, State::ClosingToPosition];
+/// Motor running in either direction
+const MOVES: &[State] = &[
+    State::OpeningContinuous,
+    State::OpeningFully,
+    State::OpeningToPosition,
+    State::ClosingContinuous,
+    State::ClosingFully,
+    State::ClosingToPosition,
+];
+/// Motor at rest and nothing left to finish, only these may start a calibration
+const AT_REST: &[State] = &[
+    State::None,
+    State::Stopped,
+    State::OpeningInterrupted,
+    State::OpeningFinished,
+    State::OpeningObstructed,
+    State::OpeningStalled,
+    State::ClosingInterrupted,
+    State::ClosingFinished,
+    State::PositionReached,
+    State::ReversedAfterObstruction,
+    State::CalibrationFinished,
+    State::CalibrationFailed,
+];
+/// Busy with something no regular command may interrupt
+const BUSY: &[State] = &[State::ReversingAfterObstruction, State::Calibrating, State::Faulted];
+
+const OPEN_COMMANDS: &[Input] = &[Input::OpenContinuous, Input::OpenFully];
+const CLOSE_COMMANDS: &[Input] = &[Input::CloseContinuous, Input::CloseFully];
+const MOVE_COMMANDS: &[Input] = &[
+    Input::OpenContinuous,
+    Input::OpenFully,
+    Input::CloseContinuous,
+    Input::CloseFully,
+    Input::MoveToPosition(TravelDirection::Opening),
+    Input::MoveToPosition(TravelDirection::Closing),
+];
+const REVERSAL_INTERRUPTS: &[Input] = &[
+    Input::Interrupt(TravelDirection::Opening, StopCause::Obstruction),
+    Input::Interrupt(TravelDirection::Opening, StopCause::Stall),
+    Input::Interrupt(TravelDirection::Opening, StopCause::OverCurrent),
+    Input::Interrupt(TravelDirection::Opening, StopCause::Overheated),
+];
+
+/// Transitions by state and input, the first matching rule wins. Inputs without a
+/// matching rule are ignored, apart from interrupts, which are unexpected then.
+///
+/// Interrupts always stop the motor and record their cause before the actions of their rule.
+const RULES: &[Rule] = &[
+    // A fault overrides everything, only clearing it gets the window out again
+    Rule {
+        from: &[State::Faulted],
+        on: &[Input::FaultsCleared],
+        next: Next::To(State::Stopped),
+        actions: &[Action::StopMotor],
+    },
+    Rule {
+        from: &[State::Faulted],
+        on: &[Input::Stop],
+        next: Next::Stay,
+        actions: &[Action::StopMotor],
+    },
+    Rule {
+        from: &[State::Faulted],
+        on: &[Input::Fault],
+        next: Next::Stay,
+        actions: &[],
+    },
+    Rule {
+        from: ANY,
+        on: &[Input::Fault],
+        next: Next::To(State::Faulted),
+        actions: &[Action::CancelCalibration, Action::ClearReversal, Action::ClearTarget, Action::StopMotor],
+    },
+    Rule {
+        from: BUSY,
+        on: MOVE_COMMANDS,
+        next: Next::Stay,
+        actions: &[],
+    },
+    // Short drop while the door is open, the glass mustn't rise into the seal meanwhile
+    Rule {
+        from: &[
+            State::ShortDropping,
+            State::ShortDropped,
+            State::OpeningFully,
+            State::ReversingAfterObstruction,
+            State::Faulted,
+        ],
+        on: &[Input::DoorOpened],
+        next: Next::Stay,
+        actions: &[],
+    },
+    Rule {
+        from: ANY,
+        on: &[Input::DoorOpened],
+        next: Next::To(State::ShortDropping),
+        actions: &[Action::CancelCalibration, Action::ClearTarget, Action::StartOpening, Action::StartDropTimer],
+    },
+    Rule {
+        from: &[State::ShortDropping, State::ShortDropped],
+        on: &[Input::CloseContinuous, Input::CloseFully, Input::MoveToPosition(TravelDirection::Closing)],
+        next: Next::Stay,
+        actions: &[],
+    },
+    Rule {
+        from: &[State::ShortDropping],
+        on: &[Input::ShortDropFinished],
+        next: Next::To(State::ShortDropped),
+        actions: &[Action::StopMotor],
+    },
+    Rule {
+        from: &[State::ShortDropping, State::ShortDropped],
+        on: &[Input::DoorClosed],
+        next: Next::To(State::ClosingFully),
+        actions: &[Action::StartClosing],
+    },
+    // Opening
+    Rule {
+        from: &[State::OpeningContinuous],
+        on: &[Input::OpenContinuous],
+        next: Next::Stay,
+        actions: &[],
+    },
+    Rule {
+        from: &[State::OpeningContinuous],
+        on: &[Input::OpenFully],
+        next: Next::To(State::OpeningFully),
+        actions: &[],
+    },
+    Rule {
+        from: &[State::OpeningFully, State::OpeningFinished],
+        on: OPEN_COMMANDS,
+        next: Next::Stay,
+        actions: &[],
+    },
+    Rule {
+        from: ANY,
+        on: &[Input::OpenContinuous],
+        next: Next::To(State::OpeningContinuous),
+        actions: &[Action::StartOpening],
+    },
+    Rule {
+        from: ANY,
+        on: &[Input::OpenFully],
+        next: Next::To(State::OpeningFully),
+        actions: &[Action::StartOpening],
+    },
+    // Closing
+    Rule {
+        from: &[State::ClosingContinuous],
+        on: &[Input::CloseContinuous],
+        next: Next::Stay,
+        actions: &[],
+    },
+    Rule {
+        from: &[State::ClosingContinuous],
+        on: &[Input::CloseFully],
+        next: Next::To(State::ClosingFully),
+        actions: &[],
+    },
+    Rule {
+        from: &[State::ClosingFully, State::ClosingFinished],
+        on: CLOSE_COMMANDS,
+        next: Next::Stay,
+        actions: &[],
+    },
+    Rule {
+        from: ANY,
+        on: &[Input::CloseContinuous],
+        next: Next::To(State::ClosingContinuous),
+        actions: &[Action::StartClosing],
+    },
+    Rule {
+        from: ANY,
+        on: &[Input::CloseFully],
+        next: Next::To(State::ClosingFully),
+        actions: &[Action::StartClosing],
+    },
+    // Moving to a position
+    Rule {
+        from: ANY,
+        on: &[Input::MoveToPosition(TravelDirection::Opening)],
+        next: Next::To(State::OpeningToPosition),
+        actions: &[Action::StartOpening],
+    },
+    Rule {
+        from: ANY,
+        on: &[Input::MoveToPosition(TravelDirection::Closing)],
+        next: Next::To(State::ClosingToPosition),
+        actions: &[Action::StartClosing],
+    },
+    Rule {
+        from: &[State::OpeningToPosition, State::ClosingToPosition],
+        on: &[Input::TargetReached],
+        next: Next::To(State::PositionReached),
+        actions: &[Action::StopMotor, Action::ClearTarget],
+    },
+    // Stopping, full and targeted movements run to their end, a dropped glass waits for the door
+    Rule {
+        from: &[
+            State::OpeningFully,
+            State::ClosingFully,
+            State::OpeningToPosition,
+            State::ClosingToPosition,
+            State::ReversingAfterObstruction,
+            State::ShortDropping,
+            State::ShortDropped,
+        ],
+        on: &[Input::Stop],
+        next: Next::Stay,
+        actions: &[],
+    },
+    Rule {
+        from: &[State::Calibrating],
+        on: &[Input::Stop],
+        next: Next::To(State::CalibrationFailed),
+        actions: &[Action::CancelCalibration, Action::StopMotor],
+    },
+    Rule {
+        from: ANY,
+        on: &[Input::Stop],
+        next: Next::To(State::Stopped),
+        actions: &[Action::StopMotor],
+    },
+    Rule {
+        from: &[State::OpeningContinuous, State::ClosingContinuous],
+        on: &[Input::ContinuousTimeout],
+        next: Next::To(State::Stopped),
+        actions: &[Action::StopMotor],
+    },
+    // Interrupts while closing
+    Rule {
+        from: CLOSING_MOVES,
+        on: &[Input::Interrupt(TravelDirection::Closing, StopCause::EndOfTravel)],
+        next: Next::To(State::ClosingFinished),
+        actions: &[Action::ClearTarget, Action::ReferenceClosed],
+    },
+    Rule {
+        from: &[State::ClosingFully],
+        on: &[Input::Interrupt(TravelDirection::Closing, StopCause::OverCurrent)],
+        next: Next::To(State::ClosingFinished),
+        actions: &[Action::ReferenceClosed],
+    },
+    Rule {
+        from: CLOSING_MOVES,
+        on: &[Input::Interrupt(TravelDirection::Closing, StopCause::OverCurrent)],
+        next: Next::To(State::ClosingInterrupted),
+        actions: &[Action::ClearTarget],
+    },
+    Rule {
+        from: CLOSING_MOVES,
+        on: &[
+            Input::Interrupt(TravelDirection::Closing, StopCause::Obstruction),
+            Input::Interrupt(TravelDirection::Closing, StopCause::Stall),
+        ],
+        next: Next::To(State::ReversingAfterObstruction),
+        actions: &[Action::ClearTarget, Action::StartReversal],
+    },
+    // Interrupts while opening
+    Rule {
+        from: OPENING_MOVES,
+        on: &[Input::Interrupt(TravelDirection::Opening, StopCause::EndOfTravel)],
+        next: Next::To(State::OpeningFinished),
+        actions: &[Action::ClearTarget, Action::ReferenceOpen],
+    },
+    Rule {
+        from: &[State::OpeningFully],
+        on: &[Input::Interrupt(TravelDirection::Opening, StopCause::OverCurrent)],
+        next: Next::To(State::OpeningFinished),
+        actions: &[Action::ReferenceOpen],
+    },
+    Rule {
+        from: OPENING_MOVES,
+        on: &[Input::Interrupt(TravelDirection::Opening, StopCause::OverCurrent)],
+        next: Next::To(State::OpeningInterrupted),
+        actions: &[Action::ClearTarget],
+    },
+    Rule {
+        from: OPENING_MOVES,
+        on: &[Input::Interrupt(TravelDirection::Opening, StopCause::Obstruction)],
+        next: Next::To(State::OpeningObstructed),
+        actions: &[Action::ClearTarget],
+    },
+    Rule {
+        from: OPENING_MOVES,
+        on: &[Input::Interrupt(TravelDirection::Opening, StopCause::Stall)],
+        next: Next::To(State::OpeningStalled),
+        actions: &[Action::ClearTarget],
+    },
+    // Interrupts while dropping, the glass still rises again unless it has gone all the way down
+    Rule {
+        from: &[State::ShortDropping],
+        on: &[Input::Interrupt(TravelDirection::Opening, StopCause::EndOfTravel)],
+        next: Next::To(State::OpeningFinished),
+        actions: &[Action::ReferenceOpen],
+    },
+    Rule {
+        from: &[State::ShortDropping],
+        on: REVERSAL_INTERRUPTS,
+        next: Next::To(State::ShortDropped),
+        actions: &[],
+    },
+    // Reversal after a closing obstruction
+    Rule {
+        from: &[State::ReversingAfterObstruction],
+        on: &[Input::Interrupt(TravelDirection::Opening, StopCause::EndOfTravel)],
+        next: Next::To(State::ReversedAfterObstruction),
+        actions: &[Action::ClearReversal, Action::ReferenceOpen],
+    },
+    Rule {
+        from: &[State::ReversingAfterObstruction],
+        on: REVERSAL_INTERRUPTS,
+        next: Next::To(State::ReversedAfterObstruction),
+        actions: &[Action::ClearReversal],
+    },
+    Rule {
+        from: &[State::ReversingAfterObstruction],
+        on: &[Input::ReversalFinished],
+        next: Next::To(State::ReversedAfterObstruction),
+        actions: &[Action::StopMotor, Action::ClearReversal],
+    },
+    // Overheating, a reversal is let finish to release whatever got trapped
+    Rule {
+        from: &[State::Calibrating],
+        on: &[Input::Overheated],
+        next: Next::To(State::CalibrationFailed),
+        actions: &[Action::CancelCalibration, Action::StopMotor],
+    },
+    Rule {
+        from: MOVES,
+        on: &[Input::Overheated],
+        next: Next::To(State::Stopped),
+        actions: &[Action::RecordStopCause(StopCause::Overheated), Action::ClearTarget, Action::StopMotor],
+    },
+    // Losing the link stops full movements too, a reversal is let finish as well
+    Rule {
+        from: &[State::Calibrating],
+        on: &[Input::LinkLost],
+        next: Next::To(State::CalibrationFailed),
+        actions: &[Action::CancelCalibration, Action::StopMotor],
+    },
+    Rule {
+        from: MOVES,
+        on: &[Input::LinkLost],
+        next: Next::To(State::Stopped),
+        actions: &[Action::RecordStopCause(StopCause::LinkLost), Action::ClearTarget, Action::StopMotor],
+    },
+    // Calibration
+    Rule {
+        from: AT_REST,
+        on: &[Input::Calibrate],
+        next: Next::To(State::Calibrating),
+        actions: &[Action::StopMotor, Action::StartCalibration, Action::StartOpening],
+    },
+    Rule {
+        from: &[State::Calibrating],
+        on: &[Input::CalibrationPhase(TravelDirection::Opening)],
+        next: Next::Stay,
+        actions: &[Action::StopMotor, Action::StartOpening],
+    },
+    Rule {
+        from: &[State::Calibrating],
+        on: &[Input::CalibrationPhase(TravelDirection::Closing)],
+        next: Next::Stay,
+        actions: &[Action::StopMotor, Action::StartClosing],
+    },
+    Rule {
+        from: &[State::Calibrating],
+        on: &[Input::CalibrationFinished],
+        next: Next::To(State::CalibrationFinished),
+        actions: &[Action::StopMotor, Action::CancelCalibration, Action::ReferenceClosed],
+    },
+    Rule {
+        from: &[State::Calibrating],
+        on: &[Input::CalibrationFailed],
+        next: Next::To(State::CalibrationFailed),
+        actions: &[Action::StopMotor, Action::CancelCalibration],
+    },
+];
+
+/// Actions of an interrupt without a rule, the motor has already been stopped
+const UNEXPECTED_INTERRUPT_ACTIONS: &[Action] = &[
+    Action::ClearTarget,
+    Action::ClearReversal,
+    Action::ReportUnexpectedTransition,
+];
+
+/// Looks up where the window goes from `state` on `input` and what has to be done on the way
+pub fn transition(state: State, input: Input) -> Transition {
+    let rule = RULES
+        .iter()
+        .find(|rule| rule.from.contains(&state) && rule.on.contains(&input));
+
+    let mut actions = match input {
+        Input::Interrupt(_, cause) => vec![Action::StopMotor, Action::RecordStopCause(cause)],
+        _ => Vec::new(),
+    };
+
+    let next = match (rule, input) {
+        (Some(rule), _) => {
+            actions.extend_from_slice(rule.actions);
+
+            match rule.next {
+                Next::Stay => state,
+                Next::To(next) => next,
+            }
+        }
+        (None, Input::Interrupt(..)) => {
+            actions.extend_from_slice(UNEXPECTED_INTERRUPT_ACTIONS);
+
+            // Nothing but clearing the faults leaves the safe state
+            match state {
+                State::Faulted => State::Faulted,
+                _ => State::Stopped,
+            }
+        }
+        (None, _) => state,
+    };
+
+    Transition { next, actions }
+}
@@ -0,0 +1,46 @@
+use shared_lib::{dto::telemetry::TelemetryEvent, units::Milliseconds};
+
+/// Current sample interval of clients which haven't asked for another one
+pub const DEFAULT_SAMPLE_INTERVAL: Milliseconds = Milliseconds(100);
+
+/// Decides which telemetry events a single client gets.
+///
+/// State changes always go through, current samples are thinned out to
+/// the client's sample interval.
+pub struct TelemetryThrottle {
+    sample_interval: Milliseconds,
+    last_sample_millis: Option<u64>,
+}
+
+impl Default for TelemetryThrottle {
+    fn default() -> Self {
+        TelemetryThrottle {
+            sample_interval: DEFAULT_SAMPLE_INTERVAL,
+            last_sample_millis: None,
+        }
+    }
+}
+
+impl TelemetryThrottle {
+    pub fn set_sample_interval(&mut self, sample_interval: Milliseconds) {
+        self.sample_interval = sample_interval.non_zero_or(DEFAULT_SAMPLE_INTERVAL);
+    }
+
+    /// Whether the event should be sent to the client, expects events in the order they happened
+    pub fn admit(&mut self, event: &TelemetryEvent) -> bool {
+        let uptime_millis = match event {
+            TelemetryEvent::State { .. } => return true,
+            TelemetryEvent::Current { uptime_millis, .. } => *uptime_millis,
+        };
+
+        let due = self.last_sample_millis.map_or(true, |last_sample_millis| {
+            uptime_millis.saturating_sub(last_sample_millis) >= self.sample_interval.0 as u64
+        });
+
+        if due {
+            self.last_sample_millis = Some(uptime_millis);
+        }
+
+        due
+    }
+}
@@ -0,0 +1,75 @@
+use shared_lib::units::Milliamps;
+
+/// Share of the limit the load has to fall below before movements are allowed again
+const RESUME_FRACTION: f32 = 0.8;
+/// Share of the limit at which a running movement gets stopped
+const HARD_LIMIT_FRACTION: f32 = 1.25;
+
+#[derive(Debug, Clone, Copy)]
+pub struct ThermalLimits {
+    /// I²t at which new movements are refused, in A²s
+    pub limit_amp2_seconds: f32,
+    /// Time constant the motor cools down with, in seconds
+    pub cooling_time_constant_secs: f32,
+}
+
+/// Estimates how hot the motor runs from the I²t it has accumulated,
+/// decaying exponentially while it cools down.
+#[derive(Default)]
+pub struct MotorThermalModel {
+    /// Accumulated I²t in A²s
+    load: f32,
+    last_sample_millis: Option<u128>,
+    locked_out: bool,
+}
+
+impl MotorThermalModel {
+    /// Feeds the motor current measured now, zero while the motor is stopped
+    pub fn sample(&mut self, current: Milliamps, limits: ThermalLimits, now_millis: u128) {
+        let elapsed_secs = match self.last_sample_millis {
+            Some(last_sample_millis) => now_millis.saturating_sub(last_sample_millis) as f32 / 1000.0,
+            None => 0.0,
+        };
+        self.last_sample_millis = Some(now_millis);
+
+        let current_amps = current.0 as f32 / 1000.0;
+
+        self.load *= (-elapsed_secs / limits.cooling_time_constant_secs.max(1.0)).exp();
+        self.load += current_amps * current_amps * elapsed_secs;
+
+        if self.load >= limits.limit_amp2_seconds {
+            if !self.locked_out {
+                log::warn!("Motor thermal limit reached, refusing movements until it has cooled down.");
+            }
+            self.locked_out = true;
+        } else if self.load <= limits.limit_amp2_seconds * RESUME_FRACTION {
+            self.locked_out = false;
+        }
+    }
+
+    /// Whether new movements have to be refused
+    pub fn is_locked_out(&self) -> bool {
+        self.locked_out
+    }
+
+    /// Whether a running movement has to be stopped right away
+    pub fn is_over_hard_limit(&self, limits: ThermalLimits) -> bool {
+        self.load >= limits.limit_amp2_seconds * HARD_LIMIT_FRACTION
+    }
+
+    pub fn load_percent(&self, limits: ThermalLimits) -> u8 {
+        (self.load / limits.limit_amp2_seconds.max(1.0) * 100.0).round().min(u8::MAX as f32) as u8
+    }
+
+    /// Seconds until movements are allowed again, zero if they are allowed
+    pub fn cooldown_secs(&self, limits: ThermalLimits) -> u16 {
+        if !self.locked_out {
+            return 0;
+        }
+
+        let resume_load = limits.limit_amp2_seconds * RESUME_FRACTION;
+        let secs = limits.cooling_time_constant_secs * (self.load / resume_load).ln();
+
+        secs.ceil().clamp(0.0, u16::MAX as f32) as u16
+    }
+}
@@ -0,0 +1,124 @@
+#[derive(Debug, Clone, Copy)]
+pub struct WindowTravelConfig {
+    /// Time the glass needs to travel from fully closed to fully open
+    pub opening_travel_millis: u32,
+    /// Time the glass needs to travel from fully open to fully closed
+    pub closing_travel_millis: u32,
+}
+
+impl Default for WindowTravelConfig {
+    fn default() -> Self {
+        WindowTravelConfig {
+            opening_travel_millis: 4000,
+            closing_travel_millis: 4000,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TravelDirection {
+    Opening,
+    Closing,
+}
+
+/// Estimates the glass position from motor run time and direction.
+///
+/// Positions are fractions, 0.0 is fully closed and 1.0 is fully open. The
+/// position is unknown until the glass has been referenced against an end stop.
+pub struct WindowPositionEstimator {
+    travel_config: WindowTravelConfig,
+
+    /// Position at the start of the current movement
+    position: Option<f32>,
+    movement: Option<(TravelDirection, u128)>,
+}
+
+impl WindowPositionEstimator {
+    pub fn new(travel_config: WindowTravelConfig) -> WindowPositionEstimator {
+        WindowPositionEstimator {
+            travel_config,
+            position: None,
+            movement: None,
+        }
+    }
+
+    pub fn travel_config(&self) -> WindowTravelConfig {
+        self.travel_config
+    }
+
+    pub fn set_travel_config(&mut self, travel_config: WindowTravelConfig, now_millis: u128) {
+        // Settle the running movement with the travel times it started with
+        self.settle(now_millis);
+        self.travel_config = travel_config;
+    }
+
+    /// Marks the motor as running in the given direction from now on
+    pub fn start(&mut self, direction: TravelDirection, now_millis: u128) {
+        self.settle(now_millis);
+        self.movement = Some((direction, now_millis));
+    }
+
+    /// Marks the motor as stopped
+    pub fn stop(&mut self, now_millis: u128) {
+        self.settle(now_millis);
+        self.movement = None;
+    }
+
+    /// Sets a known position, e.g. after the glass has hit an end stop
+    pub fn reference(&mut self, position: f32, now_millis: u128) {
+        self.position = Some(position.clamp(0.0, 1.0));
+        self.movement = self.movement.map(|(direction, _)| (direction, now_millis));
+    }
+
+    /// Replaces the run time based progress since the last settle with a measured travel,
+    /// e.g. from counted motor ripples
+    pub fn track(&mut self, travel: f32, now_millis: u128) {
+        let (direction, _) = match self.movement {
+            Some(movement) => movement,
+            None => return,
+        };
+
+        self.position = self.position.map(|position| {
+            let position = match direction {
+                TravelDirection::Opening => position + travel,
+                TravelDirection::Closing => position - travel,
+            };
+
+            position.clamp(0.0, 1.0)
+        });
+        self.movement = Some((direction, now_millis));
+    }
+
+    pub fn estimate(&self, now_millis: u128) -> Option<f32> {
+        let position = self.position?;
+
+        let (direction, started_millis) = match self.movement {
+            Some(movement) => movement,
+            None => return Some(position),
+        };
+
+        let elapsed_millis = now_millis.saturating_sub(started_millis) as f32;
+
+        let estimate = match direction {
+            TravelDirection::Opening => {
+                position + elapsed_millis / self.travel_config.opening_travel_millis as f32
+            }
+            TravelDirection::Closing => {
+                position - elapsed_millis / self.travel_config.closing_travel_millis as f32
+            }
+        };
+
+        Some(estimate.clamp(0.0, 1.0))
+    }
+
+    pub fn estimate_percent(&self, now_millis: u128) -> Option<u8> {
+        self.estimate(now_millis)
+            .map(|position| (position * 100.0).round() as u8)
+    }
+
+    /// Commits the run time based progress, later tracking only replaces progress from here on
+    pub fn settle(&mut self, now_millis: u128) {
+        self.position = self.estimate(now_millis);
+        self.movement = self.movement.map(|(direction, _)| (direction, now_millis));
+    }
+}
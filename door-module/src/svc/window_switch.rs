@@ -0,0 +1,31 @@
+use shared_lib::window_switch::PowerWindowButtonState;
+
+/// Where a movement of the window has been asked for
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum MovementSource {
+    /// The master switch or any other command of the main server
+    MainServer = 1,
+    /// The window switch on the door itself
+    LocalSwitch = 2,
+    /// The door module on its own, following the door switch or the link-loss policy
+    DoorModule = 3,
+}
+
+/// Follows the window switch on the door, telling presses from an idle switch.
+///
+/// A held switch is passed on with every reading, refreshing continuous
+/// movements like the repeated commands of the master switch do. Letting go
+/// is passed on once, an idle switch afterwards isn't.
+#[derive(Default)]
+pub struct WindowSwitchMonitor {
+    pressed: bool,
+}
+
+impl WindowSwitchMonitor {
+    pub fn update(&mut self, switch_state: PowerWindowButtonState) -> Option<PowerWindowButtonState> {
+        let released = self.pressed && switch_state == PowerWindowButtonState::None;
+        self.pressed = switch_state != PowerWindowButtonState::None;
+
+        (self.pressed || released).then_some(switch_state)
+    }
+}
@@ -0,0 +1,174 @@
+use door_module::{
+    app::events::{ServerRequest, ServerRequestType},
+    hal::{
+        power_window_driver::PowerWindowDriver,
+        sim::{
+            SimulatedClock, SimulatedCurrentSense, SimulatedRelays, SimulatedStore, SimulatedWindow,
+            SimulatedWindowConfig,
+        },
+    },
+    svc::power_windows::{PowerWindowSvc, State},
+};
+use shared_lib::{
+    dto::pw_config::{PowerWindowsConfig, DTO_SIZE},
+    units::{Milliamps, Milliseconds},
+};
+
+type SimulatedSvc = PowerWindowSvc<SimulatedRelays, SimulatedCurrentSense, SimulatedClock, SimulatedStore>;
+
+fn request(request_type: ServerRequestType, data: u8) -> ServerRequest {
+    let mut request_data = [0; DTO_SIZE];
+    request_data[0] = data;
+
+    ServerRequest { request_type, request_data }
+}
+
+/// Travel times differ from the service's uncalibrated 4000ms default
+fn window(initial_position: f32) -> SimulatedWindow {
+    SimulatedWindow::new(
+        SimulatedWindowConfig {
+            opening_travel_time: Milliseconds(3000),
+            running_current: Milliamps(500),
+            stall_current: Milliamps(3000),
+            ripple_amplitude: Milliamps(60),
+            ..Default::default()
+        },
+        initial_position,
+    )
+}
+
+fn service(window: &SimulatedWindow, ripples_per_travel: u16) -> SimulatedSvc {
+    PowerWindowSvc::new(
+        PowerWindowDriver::new(window.relays(), window.current_sense(), window.clock()),
+        SimulatedStore::default(),
+        PowerWindowsConfig {
+            opening_current_interrupt_threshold: Milliamps(1500),
+            closing_current_interrupt_threshold: Milliamps(1500),
+            ripples_per_travel,
+            ..Default::default()
+        },
+    )
+}
+
+/// Lets time pass in control loop ticks, leaving out the inputs the tests don't drive
+fn run(window: &SimulatedWindow, svc: &mut SimulatedSvc, millis: u32) {
+    for _ in 0..millis / 20 {
+        window.advance(20);
+        svc.handle_continuous_timeout().unwrap();
+        svc.handle_ripple_counting().unwrap();
+        svc.handle_position_target().unwrap();
+        svc.handle_current_interrupts().unwrap();
+    }
+}
+
+fn assert_relays_low(window: &SimulatedWindow) {
+    assert!(!window.is_opening_relay_high(), "opening relay still high");
+    assert!(!window.is_closing_relay_high(), "closing relay still high");
+}
+
+#[test]
+fn open_fully_runs_to_the_end_stop() {
+    let window = window(0.5);
+    let mut svc = service(&window, 0);
+
+    svc.handle_request(&request(ServerRequestType::OpenFully, 0)).unwrap();
+    run(&window, &mut svc, 100);
+    assert!(window.is_opening_relay_high());
+
+    run(&window, &mut svc, 5000);
+    assert!(matches!(svc.state(), State::OpeningFinished), "{:?}", svc.state());
+    assert!(window.position() > 0.99, "ended at {}", window.position());
+    assert_relays_low(&window);
+}
+
+#[test]
+fn close_fully_runs_to_the_end_stop() {
+    let window = window(0.5);
+    let mut svc = service(&window, 0);
+
+    svc.handle_request(&request(ServerRequestType::CloseFully, 0)).unwrap();
+    run(&window, &mut svc, 100);
+    assert!(window.is_closing_relay_high());
+
+    run(&window, &mut svc, 5000);
+    assert!(matches!(svc.state(), State::ClosingFinished), "{:?}", svc.state());
+    assert!(window.position() < 0.01, "ended at {}", window.position());
+    assert_relays_low(&window);
+}
+
+#[test]
+fn continuous_movement_stops_once_no_longer_refreshed() {
+    let window = window(0.5);
+    let mut svc = service(&window, 0);
+
+    // Refreshed well within the 300ms handle time threshold
+    for _ in 0..5 {
+        svc.handle_request(&request(ServerRequestType::Open, 0)).unwrap();
+        run(&window, &mut svc, 200);
+        assert!(matches!(svc.state(), State::OpeningContinuous), "{:?}", svc.state());
+    }
+
+    run(&window, &mut svc, 400);
+    assert!(matches!(svc.state(), State::Stopped), "{:?}", svc.state());
+    assert_relays_low(&window);
+
+    let stopped_at = window.position();
+    assert!(stopped_at > 0.7 && stopped_at < 1.0, "stopped at {}", stopped_at);
+
+    run(&window, &mut svc, 1000);
+    assert_eq!(window.position(), stopped_at);
+}
+
+#[test]
+fn stop_ends_a_continuous_movement() {
+    let window = window(0.5);
+    let mut svc = service(&window, 0);
+
+    svc.handle_request(&request(ServerRequestType::Close, 0)).unwrap();
+    run(&window, &mut svc, 100);
+    assert!(window.is_closing_relay_high());
+
+    svc.handle_request(&request(ServerRequestType::Stop, 0)).unwrap();
+    assert!(matches!(svc.state(), State::Stopped), "{:?}", svc.state());
+    assert_relays_low(&window);
+}
+
+#[test]
+fn stop_lets_a_full_movement_run_to_its_end() {
+    let window = window(0.5);
+    let mut svc = service(&window, 0);
+
+    svc.handle_request(&request(ServerRequestType::OpenFully, 0)).unwrap();
+    run(&window, &mut svc, 100);
+
+    svc.handle_request(&request(ServerRequestType::Stop, 0)).unwrap();
+    assert!(matches!(svc.state(), State::OpeningFully), "{:?}", svc.state());
+    assert!(window.is_opening_relay_high());
+
+    run(&window, &mut svc, 5000);
+    assert!(matches!(svc.state(), State::OpeningFinished), "{:?}", svc.state());
+}
+
+fn move_to_half_open(ripples_per_travel: u16) -> f32 {
+    let window = window(0.5);
+    let mut svc = service(&window, ripples_per_travel);
+
+    svc.handle_request(&request(ServerRequestType::CloseFully, 0)).unwrap();
+    run(&window, &mut svc, 5000);
+    assert!(matches!(svc.state(), State::ClosingFinished));
+
+    svc.handle_request(&request(ServerRequestType::MoveToPosition, 50)).unwrap();
+    run(&window, &mut svc, 5000);
+    assert!(matches!(svc.state(), State::PositionReached));
+
+    window.position()
+}
+
+#[test]
+fn ripple_counting_corrects_wrong_travel_times() {
+    let run_time_position = move_to_half_open(0);
+    let ripple_position = move_to_half_open(2000);
+
+    assert!((run_time_position - 0.5).abs() > 0.1, "run time estimate ended at {}", run_time_position);
+    assert!((ripple_position - 0.5).abs() < 0.03, "ripple counting ended at {}", ripple_position);
+}
@@ -0,0 +1,102 @@
+use std::f32::consts::PI;
+
+use door_module::svc::ripple_counter::RippleCounter;
+use shared_lib::units::Milliamps;
+
+const SAMPLE_RATE_HZ: f32 = 5000.0;
+
+/// Deterministic noise source, keeps the waveforms reproducible
+struct Noise(u32);
+
+impl Noise {
+    /// Uniform noise in `-amplitude..amplitude`
+    fn next(&mut self, amplitude: f32) -> f32 {
+        self.0 = self.0.wrapping_mul(1664525).wrapping_add(1013904223);
+        ((self.0 >> 8) as f32 / (1 << 24) as f32 * 2.0 - 1.0) * amplitude
+    }
+}
+
+/// Motor current with a sinusoidal ripple on top of `dc`, sampled at `SAMPLE_RATE_HZ`
+fn ripple_waveform(
+    seconds: f32,
+    ripple_hz: impl Fn(f32) -> f32,
+    dc: impl Fn(f32) -> f32,
+    ripple_milliamps: f32,
+    noise_milliamps: f32,
+) -> (Vec<Milliamps>, f32) {
+    let mut noise = Noise(0x5EED);
+    let mut phase = 0.0;
+    let mut samples = Vec::new();
+
+    for index in 0..(seconds * SAMPLE_RATE_HZ) as usize {
+        let time = index as f32 / SAMPLE_RATE_HZ;
+        phase += ripple_hz(time) / SAMPLE_RATE_HZ;
+
+        let current = dc(time) + ripple_milliamps * (2.0 * PI * phase).sin() + noise.next(noise_milliamps);
+        samples.push(Milliamps(current.clamp(0.0, u16::MAX as f32) as u16));
+    }
+
+    (samples, phase)
+}
+
+fn count(samples: &[Milliamps]) -> u32 {
+    let mut counter = RippleCounter::default();
+    counter.sample_burst(samples)
+}
+
+fn assert_close(counted: u32, expected: f32, tolerance: f32) {
+    let error = (counted as f32 - expected).abs() / expected;
+    assert!(
+        error <= tolerance,
+        "counted {} ripples, expected {:.0} (error {:.1}%)",
+        counted,
+        expected,
+        error * 100.0
+    );
+}
+
+#[test]
+fn counts_clean_ripple() {
+    let (samples, ripples) = ripple_waveform(1.0, |_| 400.0, |_| 3000.0, 300.0, 0.0);
+
+    assert_close(count(&samples), ripples, 0.005);
+}
+
+#[test]
+fn counts_noisy_ripple() {
+    let (samples, ripples) = ripple_waveform(2.0, |_| 600.0, |_| 3000.0, 250.0, 100.0);
+
+    assert_close(count(&samples), ripples, 0.02);
+}
+
+#[test]
+fn follows_changing_load_and_speed() {
+    // Glass binding in the seal half way: the current rises while the motor slows down
+    let (samples, ripples) = ripple_waveform(
+        3.0,
+        |time| if time < 1.5 { 500.0 } else { 350.0 },
+        |time| if time < 1.5 { 3000.0 } else { 3000.0 + 1500.0 * ((time - 1.5) / 1.5) },
+        250.0,
+        50.0,
+    );
+
+    assert_close(count(&samples), ripples, 0.02);
+}
+
+#[test]
+fn ignores_flat_current() {
+    let (samples, _) = ripple_waveform(1.0, |_| 0.0, |_| 3000.0, 0.0, 15.0);
+
+    assert_eq!(count(&samples), 0);
+}
+
+#[test]
+fn counts_across_bursts() {
+    let (samples, ripples) = ripple_waveform(1.0, |_| 450.0, |_| 2500.0, 200.0, 30.0);
+
+    let mut counter = RippleCounter::default();
+    let counted: u32 = samples.chunks(150).map(|burst| counter.sample_burst(burst)).sum();
+
+    assert_eq!(counted, counter.ripples());
+    assert_close(counted, ripples, 0.01);
+}
@@ -0,0 +1,312 @@
+use std::collections::{HashSet, VecDeque};
+
+use door_module::svc::{
+    obstruction_detector::StopCause,
+    state_machine::{transition, Action, Input, State},
+    window_position::TravelDirection,
+};
+use proptest::prelude::*;
+
+/// Random walks from the initial state, a failing walk is shrunk to the shortest one found
+fn walks() -> impl Strategy<Value = Vec<Input>> {
+    prop::collection::vec(prop::sample::select(Input::all()), 1..200)
+}
+
+/// Direction a start action drives the motor in
+fn started(action: Action) -> Option<TravelDirection> {
+    match action {
+        Action::StartOpening | Action::StartReversal => Some(TravelDirection::Opening),
+        Action::StartClosing => Some(TravelDirection::Closing),
+        _ => None,
+    }
+}
+
+fn is_running(state: State) -> bool {
+    state.motion().is_some() || state == State::Calibrating
+}
+
+fn every_transition() -> impl Iterator<Item = (State, Input)> {
+    State::ALL
+        .into_iter()
+        .flat_map(|state| Input::all().into_iter().map(move |input| (state, input)))
+}
+
+#[test]
+fn transitions_are_deterministic() {
+    for (state, input) in every_transition() {
+        assert_eq!(transition(state, input), transition(state, input), "{:?} on {:?}", state, input);
+    }
+}
+
+#[test]
+fn starts_the_motor_at_most_once_and_last() {
+    for (state, input) in every_transition() {
+        let actions = transition(state, input).actions;
+        let starts: Vec<usize> = (0..actions.len()).filter(|&i| started(actions[i]).is_some()).collect();
+
+        assert!(starts.len() <= 1, "{:?} on {:?} starts twice: {:?}", state, input, actions);
+
+        if let Some(&start) = starts.first() {
+            assert!(
+                !actions[start..].contains(&Action::StopMotor),
+                "{:?} on {:?} stops after starting: {:?}",
+                state,
+                input,
+                actions
+            );
+        }
+    }
+}
+
+#[test]
+fn coming_to_rest_stops_the_motor() {
+    for (state, input) in every_transition() {
+        let outcome = transition(state, input);
+
+        if is_running(state) && !is_running(outcome.next) {
+            assert!(
+                outcome.actions.contains(&Action::StopMotor),
+                "{:?} on {:?} rests in {:?} without stopping: {:?}",
+                state,
+                input,
+                outcome.next,
+                outcome.actions
+            );
+        }
+    }
+}
+
+#[test]
+fn changing_direction_starts_the_motor() {
+    for (state, input) in every_transition() {
+        let outcome = transition(state, input);
+
+        match outcome.next.motion() {
+            Some(direction) if state.motion() != Some(direction) => assert!(
+                outcome.actions.iter().any(|action| started(*action) == Some(direction)),
+                "{:?} on {:?} moves {:?} without starting: {:?}",
+                state,
+                input,
+                direction,
+                outcome.actions
+            ),
+            _ => {}
+        }
+    }
+}
+
+#[test]
+fn fault_enters_the_safe_state_from_everywhere() {
+    for state in State::ALL {
+        let outcome = transition(state, Input::Fault);
+
+        assert_eq!(outcome.next, State::Faulted, "{:?}", state);
+
+        if state != State::Faulted {
+            assert!(outcome.actions.contains(&Action::StopMotor), "{:?}", state);
+            assert!(outcome.actions.contains(&Action::CancelCalibration), "{:?}", state);
+        }
+    }
+}
+
+#[test]
+fn faulted_is_only_left_when_faults_are_cleared() {
+    for input in Input::all() {
+        let outcome = transition(State::Faulted, input);
+
+        assert!(
+            outcome.actions.iter().all(|action| started(*action).is_none()),
+            "{:?} starts the motor while faulted",
+            input
+        );
+
+        match input {
+            Input::FaultsCleared => assert_eq!(outcome.next, State::Stopped),
+            _ => assert_eq!(outcome.next, State::Faulted, "{:?} leaves the safe state", input),
+        }
+    }
+}
+
+#[test]
+fn faults_cleared_is_ignored_unless_faulted() {
+    for state in State::ALL.into_iter().filter(|state| *state != State::Faulted) {
+        assert!(transition(state, Input::FaultsCleared).is_ignored(state), "{:?}", state);
+    }
+}
+
+#[test]
+fn interrupts_stop_and_record_their_cause_first() {
+    for (state, input) in every_transition() {
+        if let Input::Interrupt(_, cause) = input {
+            let actions = transition(state, input).actions;
+
+            assert_eq!(
+                actions[..2],
+                [Action::StopMotor, Action::RecordStopCause(cause)],
+                "{:?} on {:?}",
+                state,
+                input
+            );
+        }
+    }
+}
+
+#[test]
+fn unexpected_interrupts_are_reported() {
+    let outcome = transition(State::Stopped, Input::Interrupt(TravelDirection::Closing, StopCause::Obstruction));
+
+    assert_eq!(outcome.next, State::Stopped);
+    assert!(outcome.actions.contains(&Action::ReportUnexpectedTransition));
+
+    let outcome = transition(State::OpeningFully, Input::Interrupt(TravelDirection::Closing, StopCause::Stall));
+
+    assert_eq!(outcome.next, State::Stopped);
+    assert!(outcome.actions.contains(&Action::ReportUnexpectedTransition));
+}
+
+#[test]
+fn every_state_is_reachable() {
+    let mut reached = HashSet::from([State::None]);
+    let mut queue = VecDeque::from([State::None]);
+
+    while let Some(state) = queue.pop_front() {
+        for input in Input::all() {
+            let next = transition(state, input).next;
+
+            if reached.insert(next) {
+                queue.push_back(next);
+            }
+        }
+    }
+
+    for state in State::ALL {
+        assert!(reached.contains(&state), "{:?} unreachable", state);
+    }
+}
+
+#[test]
+fn interrupted_window_can_be_moved_on() {
+    let outcome = transition(State::OpeningInterrupted, Input::OpenContinuous);
+    assert_eq!(outcome.next, State::OpeningContinuous);
+    assert_eq!(outcome.actions, [Action::StartOpening]);
+
+    let outcome = transition(State::ClosingInterrupted, Input::CloseFully);
+    assert_eq!(outcome.next, State::ClosingFully);
+    assert_eq!(outcome.actions, [Action::StartClosing]);
+}
+
+#[test]
+fn closing_obstruction_reverses() {
+    let outcome = transition(
+        State::ClosingContinuous,
+        Input::Interrupt(TravelDirection::Closing, StopCause::Obstruction),
+    );
+
+    assert_eq!(outcome.next, State::ReversingAfterObstruction);
+    assert_eq!(outcome.actions.last(), Some(&Action::StartReversal));
+    assert!(transition(State::ReversingAfterObstruction, Input::Stop).is_ignored(State::ReversingAfterObstruction));
+}
+
+#[test]
+fn link_loss_stops_full_movements_but_lets_reversals_finish() {
+    for state in [State::OpeningFully, State::ClosingFully, State::OpeningToPosition, State::ClosingContinuous] {
+        let outcome = transition(state, Input::LinkLost);
+
+        assert_eq!(outcome.next, State::Stopped, "{:?}", state);
+        assert!(outcome.actions.contains(&Action::StopMotor), "{:?}", state);
+    }
+
+    assert!(transition(State::ReversingAfterObstruction, Input::LinkLost).is_ignored(State::ReversingAfterObstruction));
+    assert!(transition(State::Stopped, Input::LinkLost).is_ignored(State::Stopped));
+}
+
+#[test]
+fn short_drop_keeps_the_glass_off_the_seal_until_the_door_shuts() {
+    let outcome = transition(State::ClosingFinished, Input::DoorOpened);
+    assert_eq!(outcome.next, State::ShortDropping);
+    assert!(outcome.actions.contains(&Action::StartOpening));
+
+    assert_eq!(transition(State::ShortDropping, Input::ShortDropFinished).next, State::ShortDropped);
+
+    for input in [Input::Stop, Input::CloseFully, Input::CloseContinuous, Input::Calibrate, Input::DoorOpened] {
+        assert!(transition(State::ShortDropped, input).is_ignored(State::ShortDropped), "{:?}", input);
+    }
+
+    let outcome = transition(State::ShortDropped, Input::DoorClosed);
+    assert_eq!(outcome.next, State::ClosingFully);
+    assert_eq!(outcome.actions, [Action::StartClosing]);
+}
+
+#[test]
+fn calibration_only_starts_at_rest() {
+    let outcome = transition(State::ClosingFinished, Input::Calibrate);
+    assert_eq!(outcome.next, State::Calibrating);
+    assert_eq!(outcome.actions.last(), Some(&Action::StartOpening));
+
+    // The raise after the door shut is a full close, it mustn't be cut short either
+    for state in [
+        State::ReversingAfterObstruction,
+        State::ShortDropping,
+        State::ShortDropped,
+        State::ClosingFully,
+        State::OpeningContinuous,
+        State::Calibrating,
+        State::Faulted,
+    ] {
+        assert!(transition(state, Input::Calibrate).is_ignored(state), "{:?}", state);
+    }
+}
+
+proptest! {
+    #![proptest_config(ProptestConfig::with_cases(500))]
+
+    #[test]
+    fn random_walks_keep_the_motor_in_line_with_the_state(walk in walks()) {
+        let mut state = State::None;
+        let mut motor: Option<TravelDirection> = None;
+
+        for (step, input) in walk.into_iter().enumerate() {
+            let outcome = transition(state, input);
+
+            for action in &outcome.actions {
+                match action {
+                    Action::StopMotor => motor = None,
+                    action => motor = started(*action).or(motor),
+                }
+            }
+
+            state = outcome.next;
+
+            match state {
+                State::Calibrating => {
+                    prop_assert!(motor.is_some(), "step {}: calibrating with the motor stopped", step)
+                }
+                state => prop_assert_eq!(
+                    motor,
+                    state.motion(),
+                    "step {}: {:?} on {:?} leaves the motor {:?} in {:?}",
+                    step,
+                    input,
+                    outcome.actions,
+                    motor,
+                    state
+                ),
+            }
+        }
+    }
+
+    #[test]
+    fn random_walks_only_leave_the_safe_state_when_cleared(walk in walks()) {
+        let mut state = State::None;
+
+        for (step, input) in walk.into_iter().enumerate() {
+            let outcome = transition(state, input);
+
+            if state == State::Faulted && input != Input::FaultsCleared {
+                prop_assert_eq!(outcome.next, State::Faulted, "step {}: left on {:?}", step, input);
+            }
+
+            state = outcome.next;
+        }
+    }
+}
@@ -1,2 +1,4 @@
 pub mod app_state;
-pub mod request_type;
\ No newline at end of file
+pub mod passenger_lockout;
+pub mod request_type;
+pub mod top_sequence;
\ No newline at end of file
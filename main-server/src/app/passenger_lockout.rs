@@ -0,0 +1,58 @@
+/// How long a new reading of the console button has to hold before it counts
+const BUTTON_DEBOUNCE_MILLIS: u128 = 50;
+
+/// Lockout of the window switches on the passenger doors, owned by the main server.
+///
+/// Every settled press of the console button toggles it, BLE sets it directly.
+/// Holding the button doesn't toggle it again, it has to be let go first.
+pub struct PassengerLockout {
+    locked: bool,
+    button_pressed: bool,
+    button_changing_since_millis: Option<u128>,
+}
+
+impl PassengerLockout {
+    /// Starts out with the given lockout, e.g. the persisted one
+    pub fn new(locked: bool) -> PassengerLockout {
+        PassengerLockout {
+            locked,
+            button_pressed: false,
+            button_changing_since_millis: None,
+        }
+    }
+
+    pub fn is_locked(&self) -> bool {
+        self.locked
+    }
+
+    /// Locks or unlocks, returns whether the lockout has changed
+    pub fn set(&mut self, locked: bool) -> bool {
+        let changed = self.locked != locked;
+        self.locked = locked;
+        changed
+    }
+
+    /// Feeds a reading of the console button, returns whether the lockout has been toggled
+    pub fn update_button(&mut self, pressed: bool, now_millis: u128) -> bool {
+        if self.button_pressed == pressed {
+            self.button_changing_since_millis = None;
+            return false;
+        }
+
+        let changing_since_millis = *self.button_changing_since_millis.get_or_insert(now_millis);
+
+        if now_millis.saturating_sub(changing_since_millis) < BUTTON_DEBOUNCE_MILLIS {
+            return false;
+        }
+
+        self.button_pressed = pressed;
+        self.button_changing_since_millis = None;
+
+        if !pressed {
+            return false;
+        }
+
+        self.locked = !self.locked;
+        true
+    }
+}
@@ -0,0 +1,219 @@
+use shared_lib::dto::pw_status::{
+    PowerWindowStatus, STATE_CLOSING_FINISHED, STATE_CLOSING_FULLY, STATE_FAULTED, STATE_OPENING_FINISHED,
+    STATE_OPENING_FULLY,
+};
+
+use crate::clients::types::ClientType;
+
+/// How long a door may take to pick up a command, a window not moving afterwards stopped short
+const DOOR_SETTLE_MILLIS: u128 = 1000;
+/// How long a window may take to travel all the way
+const DOOR_TRAVEL_TIMEOUT_MILLIS: u128 = 10_000;
+
+/// Latest report of a door taking part in the sequence
+#[derive(Debug, Clone, Copy)]
+pub enum DoorReport {
+    /// The door couldn't be reached
+    Offline,
+    Status(PowerWindowStatus),
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TopSequenceCommand {
+    /// Drives the window of the door all the way down
+    LowerWindow(ClientType),
+    /// Drives the window of the door all the way up
+    RaiseWindow(ClientType),
+    /// Stops the windows of every door taking part in the sequence
+    StopWindows,
+    /// Lets the top move
+    ReleaseTop,
+    /// Keeps the top from moving
+    HoldTop,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TopSequencePhase {
+    /// Waiting for the top switch
+    Idle,
+    /// Lowering the window of the door at the given index of the sequence
+    Lowering { door: usize, since_millis: u128 },
+    /// Every window is down, the top may move while the switch is held
+    TopReleased,
+    /// Raising the window of the door at the given index of the sequence
+    Raising { door: usize, since_millis: u128 },
+    /// Something went wrong, nothing is started again until the top switch has been let go
+    Aborted,
+}
+
+/// Sequences the windows around a move of the convertible top.
+///
+/// Holding the top switch lowers the window of every door one after another,
+/// each one has to report its glass at the lower end stop before the next one
+/// starts. The top is only released once every window is down and is held
+/// again as soon as the switch is let go, the windows are raised afterwards
+/// if asked for. A door going offline or reporting a critical fault aborts
+/// the sequence, holding the top and stopping the windows.
+pub struct TopSequence {
+    doors: Vec<ClientType>,
+    raise_afterwards: bool,
+    phase: TopSequencePhase,
+}
+
+impl TopSequence {
+    /// Creates the sequence for `doors`, which are lowered and raised in the given order
+    pub fn new(doors: Vec<ClientType>, raise_afterwards: bool) -> TopSequence {
+        TopSequence {
+            doors,
+            raise_afterwards,
+            phase: TopSequencePhase::Idle,
+        }
+    }
+
+    pub fn doors(&self) -> &[ClientType] {
+        &self.doors
+    }
+
+    pub fn phase(&self) -> TopSequencePhase {
+        self.phase
+    }
+
+    /// Whether the doors are watched, `update` needs their reports then
+    pub fn is_running(&self) -> bool {
+        matches!(
+            self.phase,
+            TopSequencePhase::Lowering { .. } | TopSequencePhase::TopReleased | TopSequencePhase::Raising { .. }
+        )
+    }
+
+    /// Steps the sequence, `reports` holds the latest report of every door in the order of `doors`
+    pub fn update(&mut self, top_switch: bool, reports: &[DoorReport], now_millis: u128) -> Vec<TopSequenceCommand> {
+        if self.is_running() {
+            if let Err(reason) = self.check_doors(reports) {
+                return self.abort(reason);
+            }
+        }
+
+        match self.phase {
+            TopSequencePhase::Idle if top_switch => {
+                log::info!("Top switch pressed, lowering the windows...");
+                self.lower(0, now_millis)
+            }
+            TopSequencePhase::Idle => Vec::new(),
+            TopSequencePhase::Lowering { .. } if !top_switch => self.abort("Top switch let go".to_string()),
+            TopSequencePhase::Lowering { door, since_millis } => {
+                match Self::progress(reports[door], STATE_OPENING_FULLY, STATE_OPENING_FINISHED, since_millis, now_millis) {
+                    Ok(true) => self.lower(door + 1, now_millis),
+                    Ok(false) => Vec::new(),
+                    Err(reason) => self.abort(format!("{:?} {}", self.doors[door], reason)),
+                }
+            }
+            TopSequencePhase::TopReleased if top_switch => Vec::new(),
+            TopSequencePhase::TopReleased => {
+                log::info!("Top switch let go, holding the top.");
+
+                let mut commands = vec![TopSequenceCommand::HoldTop];
+                match self.raise_afterwards {
+                    true => commands.extend(self.raise(0, now_millis)),
+                    false => self.phase = TopSequencePhase::Idle,
+                }
+                commands
+            }
+            TopSequencePhase::Raising { door, since_millis } => {
+                match Self::progress(reports[door], STATE_CLOSING_FULLY, STATE_CLOSING_FINISHED, since_millis, now_millis) {
+                    Ok(true) => self.raise(door + 1, now_millis),
+                    Ok(false) => Vec::new(),
+                    Err(reason) => self.abort(format!("{:?} {}", self.doors[door], reason)),
+                }
+            }
+            TopSequencePhase::Aborted => {
+                if !top_switch {
+                    self.phase = TopSequencePhase::Idle;
+                }
+                Vec::new()
+            }
+        }
+    }
+
+    /// Every door has to be reachable and free of critical faults while the sequence runs
+    fn check_doors(&self, reports: &[DoorReport]) -> Result<(), String> {
+        for (index, door) in self.doors.iter().enumerate() {
+            match reports.get(index).copied().unwrap_or(DoorReport::Offline) {
+                DoorReport::Offline => return Err(format!("{:?} offline", door)),
+                DoorReport::Status(status) if status.state == STATE_FAULTED => {
+                    return Err(format!("{:?} faulted with fault {:?}", door, status.fault))
+                }
+                DoorReport::Status(_) => {}
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Whether the window has arrived in `finished`, failing once it isn't in `moving` after settling
+    fn progress(
+        report: DoorReport,
+        moving: u8,
+        finished: u8,
+        since_millis: u128,
+        now_millis: u128,
+    ) -> Result<bool, &'static str> {
+        let state = match report {
+            DoorReport::Status(status) => status.state,
+            DoorReport::Offline => return Err("offline"),
+        };
+
+        if state == finished {
+            return Ok(true);
+        }
+
+        let elapsed_millis = now_millis.saturating_sub(since_millis);
+
+        if state != moving && elapsed_millis >= DOOR_SETTLE_MILLIS {
+            return Err("window stopped short of its end stop");
+        }
+
+        if elapsed_millis >= DOOR_TRAVEL_TIMEOUT_MILLIS {
+            return Err("window took too long");
+        }
+
+        Ok(false)
+    }
+
+    fn lower(&mut self, door: usize, now_millis: u128) -> Vec<TopSequenceCommand> {
+        match self.doors.get(door) {
+            Some(client_type) => {
+                log::info!("Lowering the {:?} window...", client_type);
+                self.phase = TopSequencePhase::Lowering { door, since_millis: now_millis };
+                vec![TopSequenceCommand::LowerWindow(*client_type)]
+            }
+            None => {
+                log::info!("Every window is down, releasing the top...");
+                self.phase = TopSequencePhase::TopReleased;
+                vec![TopSequenceCommand::ReleaseTop]
+            }
+        }
+    }
+
+    fn raise(&mut self, door: usize, now_millis: u128) -> Vec<TopSequenceCommand> {
+        match self.doors.get(door) {
+            Some(client_type) => {
+                log::info!("Raising the {:?} window...", client_type);
+                self.phase = TopSequencePhase::Raising { door, since_millis: now_millis };
+                vec![TopSequenceCommand::RaiseWindow(*client_type)]
+            }
+            None => {
+                log::info!("Every window is up again, top sequence finished.");
+                self.phase = TopSequencePhase::Idle;
+                Vec::new()
+            }
+        }
+    }
+
+    fn abort(&mut self, reason: String) -> Vec<TopSequenceCommand> {
+        log::error!("Top sequence aborted: {}", reason);
+
+        self.phase = TopSequencePhase::Aborted;
+        vec![TopSequenceCommand::HoldTop, TopSequenceCommand::StopWindows]
+    }
+}
@@ -4,15 +4,16 @@ use embedded_svc::utils::mutex::Mutex;
 use esp32_nimble::{
     utilities::mutex::RawMutex, uuid128, BLECharacteristic, BLEDevice, BLEService, NimbleProperties, enums::{AuthReq, SecurityIOCap},
 };
-use shared_lib::dto::pw_config::{PowerWindowsConfig, Deserialize};
-use tokio::sync::broadcast;
+use shared_lib::dto::pw_config::{PowerWindowsConfig, Deserialize, DTO_SIZE};
+use tokio::sync::{broadcast, watch};
 
-use super::config::{DEBUG_NOTIFYING_UUID, PW_CFG_UUID};
+use super::config::{DEBUG_NOTIFYING_UUID, PW_CFG_UUID, PW_LOCKOUT_UUID};
 
 pub struct BluetoothServer {
     pub service: Arc<Mutex<RawMutex, BLEService>>,
     pub debug_characteristic: Arc<Mutex<RawMutex, BLECharacteristic>>,
     pub pw_cfg_characteristic: Arc<Mutex<RawMutex, BLECharacteristic>>,
+    pub pw_lockout_characteristic: Arc<Mutex<RawMutex, BLECharacteristic>>,
 }
 
 impl BluetoothServer {
@@ -66,29 +67,41 @@ impl BluetoothServer {
             NimbleProperties::READ | NimbleProperties::WRITE | NimbleProperties::READ_ENC | NimbleProperties::READ_AUTHEN,
         );
     
+        let pw_lockout_characteristic = service.lock().create_characteristic(
+            PW_LOCKOUT_UUID,
+            NimbleProperties::READ
+                | NimbleProperties::WRITE
+                | NimbleProperties::NOTIFY
+                | NimbleProperties::READ_ENC
+                | NimbleProperties::READ_AUTHEN,
+        );
+    
         Ok(BluetoothServer {
             service,
             debug_characteristic,
-            pw_cfg_characteristic
+            pw_cfg_characteristic,
+            pw_lockout_characteristic,
         })
     }
 
-    pub fn setup(self, config_sender: broadcast::Sender<PowerWindowsConfig>) {
+    /// Hooks up the characteristics, the lockout one reads and notifies the lockout as it changes
+    /// and is written with a single byte, 0 unlocks and anything else locks
+    pub fn setup(
+        self,
+        config_sender: broadcast::Sender<PowerWindowsConfig>,
+        lockout_sender: broadcast::Sender<bool>,
+        mut lockout_receiver: watch::Receiver<bool>,
+    ) {
         let mut pw_cfg = self.pw_cfg_characteristic.lock();
         
         pw_cfg.on_write(move |value| {
-            if value.recv_data.len() != 8 {
+            if value.recv_data.len() > DTO_SIZE {
                 log::error!("Invalid config size: {}", value.recv_data.len());
                 return;
             }
 
-            let pw_cfg_raw: [u8; 8] = match value.recv_data.try_into() {
-                Ok(pw_cfg_raw) => pw_cfg_raw,
-                Err(e) => {
-                    log::error!("Error: {:?}", e);
-                    return;
-                }
-            };
+            let mut pw_cfg_raw: [u8; DTO_SIZE] = [0; DTO_SIZE];
+            pw_cfg_raw[..value.recv_data.len()].copy_from_slice(value.recv_data);
 
             match config_sender.send(PowerWindowsConfig::deserialize(pw_cfg_raw)) {
                 Ok(_) => log::info!("Sent config to clients"),
@@ -97,5 +110,34 @@ impl BluetoothServer {
         });
 
         drop(pw_cfg);
+
+        let mut pw_lockout = self.pw_lockout_characteristic.lock();
+
+        pw_lockout.set_value(&[*lockout_receiver.borrow() as u8]);
+        pw_lockout.on_write(move |value| {
+            let locked = match value.recv_data.first() {
+                Some(locked) => *locked != 0,
+                None => {
+                    log::error!("Empty lockout request");
+                    return;
+                }
+            };
+
+            match lockout_sender.send(locked) {
+                Ok(_) => log::info!("Sent passenger lockout request"),
+                Err(e) => log::error!("Error: {:?}", e)
+            }
+        });
+
+        drop(pw_lockout);
+
+        let pw_lockout_characteristic = self.pw_lockout_characteristic.clone();
+        tokio::spawn(async move {
+            while lockout_receiver.changed().await.is_ok() {
+                let locked = *lockout_receiver.borrow();
+
+                pw_lockout_characteristic.lock().set_value(&[locked as u8]).notify();
+            }
+        });
     }
 }
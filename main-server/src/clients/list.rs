@@ -1,6 +1,9 @@
-use shared_lib::wifi::ext::ApClientInfo;
+use shared_lib::wifi::types::ApClientInfo;
 
-use super::types::ClientType;
+use super::{
+  addresses::get_mac_for_client_type,
+  types::{ClientType, CLIENT_TYPES},
+};
 
 #[derive(Debug, Clone, Copy, Default)]
 pub struct ClientsList {
@@ -32,4 +35,55 @@ impl ClientsList {
       _ => (),
     }
   }
+
+  /// Matches the clients connected to the AP against the known client addresses,
+  /// returns whether any client has connected, disconnected or changed IP
+  pub fn update_from_ap_clients(&mut self, clients: &[Option<ApClientInfo>]) -> bool {
+    let mut any_change = false;
+
+    for client_type in CLIENT_TYPES {
+      let client_mac = match get_mac_for_client_type(client_type) {
+        Some(mac) => mac,
+        None => continue,
+      };
+
+      let existing_client = self.get_client_for_type(client_type);
+      let found_client = clients
+        .iter()
+        .flatten()
+        .find(|client| client.mac == client_mac)
+        .copied();
+
+      match (existing_client, found_client) {
+        (Some(existing_client), Some(found_client)) => {
+          if existing_client.ip != found_client.ip {
+            log::info!(
+              "Client {:?} has changed IP from {:?} to {:?}",
+              client_type,
+              existing_client.ip,
+              found_client.ip
+            );
+
+            self.set_client_for_type(client_type, found_client);
+            any_change = true;
+          } else {
+            log::debug!("Client {:?} has not changed", client_type);
+          }
+        }
+        (None, Some(found_client)) => {
+          log::info!("Client {:?} has connected", client_type);
+          self.set_client_for_type(client_type, found_client);
+          any_change = true;
+        }
+        (Some(_), None) => {
+          log::info!("Client {:?} has disconnected", client_type);
+          self.remove_client_for_type(client_type);
+          any_change = true;
+        }
+        _ => {}
+      }
+    }
+
+    any_change
+  }
 }
\ No newline at end of file
@@ -0,0 +1,21 @@
+use esp_idf_hal::gpio::{Gpio21, Input, PinDriver, Pull};
+use esp_idf_sys::EspError;
+
+/// Console button of the driver toggling the passenger lockout
+pub struct LockoutButton {
+    pin: PinDriver<'static, Gpio21, Input>,
+}
+
+impl LockoutButton {
+    pub fn new(pin: Gpio21) -> Result<LockoutButton, EspError> {
+        let mut pin = PinDriver::input(pin)?;
+        pin.set_pull(Pull::Up)?;
+
+        Ok(LockoutButton { pin })
+    }
+
+    /// Whether the driver presses the button, it pulls the input low
+    pub fn is_pressed(&self) -> bool {
+        self.pin.is_low()
+    }
+}
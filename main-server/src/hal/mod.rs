@@ -1,13 +1,24 @@
+#[cfg(feature = "esp")]
 use esp_idf_hal::{
     adc::ADC1,
     gpio::{Gpio2, Gpio3, Gpio4, Gpio5},
 };
 
+#[cfg(feature = "esp")]
+pub mod lockout_button;
+#[cfg(feature = "esp")]
 mod power_window_input;
+#[cfg(feature = "esp")]
 pub mod power_window_controls_driver;
+#[cfg(feature = "esp")]
+pub mod top_control;
 
+#[cfg(feature = "esp")]
 pub type DefaultPowerWindowPeripherals = power_window_input::RequiredPeripherals<ADC1, Gpio2, Gpio3, Gpio4, Gpio5>;
 
+#[cfg(feature = "esp")]
 pub type DefaultRightRequiredButtonPins = power_window_input::RequiredButtonPins<Gpio2, Gpio3>;
+#[cfg(feature = "esp")]
 pub type DefaultLeftRequiredButtonPins = power_window_input::RequiredButtonPins<Gpio4, Gpio5>;
+#[cfg(feature = "esp")]
 pub type DefaultPowerWindowPins = power_window_input::PowerWindowPins<ADC1, Gpio2, Gpio3, Gpio4, Gpio5>;
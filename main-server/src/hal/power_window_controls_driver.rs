@@ -1,69 +1,84 @@
 use esp_idf_sys::EspError;
+use shared_lib::sampling::{
+    esp_clock::EspSamplingClock,
+    filter::FilterConfig,
+    service::{ChannelHandle, ChannelReading, SamplingConfig, SamplingService},
+};
+use shared_lib::units::Millivolts;
+use shared_lib::window_switch::{get_state_for_voltages, PowerWindowButtonState};
+use tokio::sync::watch;
 
 use super::{
-    power_window_input::prepare_input_pins, DefaultPowerWindowPeripherals, DefaultPowerWindowPins,
+    power_window_input::{
+        prepare_input_pins, LEFT_CLOSE_CHANNEL, LEFT_OPEN_CHANNEL, RIGHT_CLOSE_CHANNEL, RIGHT_OPEN_CHANNEL,
+    },
+    DefaultPowerWindowPeripherals,
 };
 
-pub struct PowerWindowDriver {
-    input: DefaultPowerWindowPins,
-}
-
-const VOLTAGE_CONTINOUS_THRESHOLD: u16 = 300;
-const VOLTAGE_FULL_THRESHOLD: u16 = 700;
+const BUTTON_SAMPLE_INTERVAL_MICROS: u32 = 10_000;
+/// Removes contact bounce and spikes, a press is recognised after three samples
+const BUTTON_FILTER: FilterConfig = FilterConfig {
+    median_window: 5,
+    iir_alpha: 0.5,
+};
+const SAMPLING_STACK_SIZE: usize = 4096;
 
-pub enum PowerWindowButtonState {
-    None = 0,
-    OpenContinuous = 0b001,
-    CloseContinuous = 0b010,
-    OpenFully = 0b101,
-    CloseFully = 0b110,
+pub struct PowerWindowDriver {
+    left_open: ChannelHandle,
+    left_close: ChannelHandle,
+    right_open: ChannelHandle,
+    right_close: ChannelHandle,
+    samples: watch::Receiver<ChannelReading>,
 }
 
 impl PowerWindowDriver {
+    /// Starts sampling the button inputs on a separate thread
     pub fn new(pins: DefaultPowerWindowPeripherals) -> Result<PowerWindowDriver, EspError> {
-        Ok(PowerWindowDriver {
-            input: prepare_input_pins(pins)?,
-        })
-    }
+        let sampling = SamplingService::new(
+            prepare_input_pins(pins)?,
+            EspSamplingClock,
+            SamplingConfig {
+                interval_micros: BUTTON_SAMPLE_INTERVAL_MICROS,
+                filters: vec![BUTTON_FILTER; 4],
+            },
+        );
 
-    /// Reads the current state of left button
-    pub fn read_left_button_state(&mut self) -> Result<PowerWindowButtonState, EspError> {
-        let open = self.input.read_pw_l_open()?;
-        let close = self.input.read_pw_l_close()?;
+        let left_open = sampling.channel(LEFT_OPEN_CHANNEL);
+        let driver = PowerWindowDriver {
+            samples: left_open.subscribe(),
+            left_open,
+            left_close: sampling.channel(LEFT_CLOSE_CHANNEL),
+            right_open: sampling.channel(RIGHT_OPEN_CHANNEL),
+            right_close: sampling.channel(RIGHT_CLOSE_CHANNEL),
+        };
 
-        Ok(Self::get_state_for_voltages(open, close))
-    }
-
-    /// Reads the current state of right button
-    pub fn read_right_button_state(&mut self) -> Result<PowerWindowButtonState, EspError> {
-      let open = self.input.read_pw_r_open()?;
-      let close = self.input.read_pw_r_close()?;
+        if let Err(err) = sampling.spawn(SAMPLING_STACK_SIZE) {
+            log::error!("Couldn't start button sampling: {:?}", err);
+            panic!("Couldn't start button sampling: {:?}", err);
+        }
 
-      Ok(Self::get_state_for_voltages(open, close))
+        Ok(driver)
     }
 
-    fn get_state_for_voltages(open: u16, close: u16) -> PowerWindowButtonState {
-        if open > VOLTAGE_CONTINOUS_THRESHOLD && close > VOLTAGE_CONTINOUS_THRESHOLD {
-            log::warn!("Both buttons are pressed at the same time!");
-            return PowerWindowButtonState::None;
-        }
-
-        if open > VOLTAGE_CONTINOUS_THRESHOLD {
-            if open > VOLTAGE_FULL_THRESHOLD {
-                return PowerWindowButtonState::OpenFully;
-            } else {
-                return PowerWindowButtonState::OpenContinuous;
-            }
+    /// Waits until the buttons have been sampled again
+    pub async fn wait_for_sample(&mut self) {
+        if self.samples.changed().await.is_err() {
+            panic!("Button sampling stopped!");
         }
+    }
 
-        if close > VOLTAGE_CONTINOUS_THRESHOLD {
-            if close > VOLTAGE_FULL_THRESHOLD {
-                return PowerWindowButtonState::CloseFully;
-            } else {
-                return PowerWindowButtonState::CloseContinuous;
-            }
-        }
+    /// Reads the current state of left button
+    pub fn read_left_button_state(&self) -> PowerWindowButtonState {
+        get_state_for_voltages(voltage(&self.left_open), voltage(&self.left_close))
+    }
 
-        PowerWindowButtonState::None
+    /// Reads the current state of right button
+    pub fn read_right_button_state(&self) -> PowerWindowButtonState {
+        get_state_for_voltages(voltage(&self.right_open), voltage(&self.right_close))
     }
 }
+
+/// Filtered button output voltage, the ADC is calibrated to read mV
+fn voltage(channel: &ChannelHandle) -> Millivolts {
+    Millivolts(channel.latest().filtered)
+}
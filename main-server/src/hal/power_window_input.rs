@@ -7,10 +7,15 @@ use esp_idf_hal::{
     gpio::ADCPin,
 };
 use esp_idf_sys::EspError;
-use shared_lib::hal::adc::create_adc_pin_driver_atten_db11;
+use shared_lib::{hal::adc::create_adc_pin_driver_atten_db11, sampling::service::AdcSource};
 
 use super::{DefaultPowerWindowPeripherals, DefaultPowerWindowPins};
 
+pub const LEFT_OPEN_CHANNEL: usize = 0;
+pub const LEFT_CLOSE_CHANNEL: usize = 1;
+pub const RIGHT_OPEN_CHANNEL: usize = 2;
+pub const RIGHT_CLOSE_CHANNEL: usize = 3;
+
 pub struct RequiredButtonPins<TOpenPin, TClosePin>
 where
     TOpenPin: ADCPin,
@@ -54,41 +59,29 @@ where
     right_pw: PowerWindowButtonPins<TROpenPin, TRClosePin>,
 }
 
-impl<TADC, TROpenPin, TRClosePin, TLOpenPin, TLClosePin>
-    PowerWindowPins<TADC, TROpenPin, TRClosePin, TLOpenPin, TLClosePin>
+impl<TADC, TROpenPin, TRClosePin, TLOpenPin, TLClosePin> AdcSource
+    for PowerWindowPins<TADC, TROpenPin, TRClosePin, TLOpenPin, TLClosePin>
 where
     TADC: Adc + 'static,
-    TLOpenPin: ADCPin<Adc = TADC>,
-    TLClosePin: ADCPin<Adc = TADC>,
-    TROpenPin: ADCPin<Adc = TADC>,
-    TRClosePin: ADCPin<Adc = TADC>,
+    TLOpenPin: ADCPin<Adc = TADC> + Send,
+    TLClosePin: ADCPin<Adc = TADC> + Send,
+    TROpenPin: ADCPin<Adc = TADC> + Send,
+    TRClosePin: ADCPin<Adc = TADC> + Send,
 {
-    /// Reads the voltage of left open button output
-    pub fn read_pw_l_open(&mut self) -> Result<u16, EspError> {
-        let reading = self.adc_driver.read(&mut self.left_pw.open_pin)?;
+    type Error = EspError;
 
-        Ok(reading)
+    fn channel_count(&self) -> usize {
+        4
     }
 
-    /// Reads the voltage of left close button output
-    pub fn read_pw_l_close(&mut self) -> Result<u16, EspError> {
-        let reading = self.adc_driver.read(&mut self.left_pw.close_pin)?;
-
-        Ok(reading)
-    }
-
-    /// Reads the voltage of right open button output
-    pub fn read_pw_r_open(&mut self) -> Result<u16, EspError> {
-        let reading = self.adc_driver.read(&mut self.right_pw.open_pin)?;
-
-        Ok(reading)
-    }
-
-    /// Reads the voltage of right close button output
-    pub fn read_pw_r_close(&mut self) -> Result<u16, EspError> {
-        let reading = self.adc_driver.read(&mut self.right_pw.close_pin)?;
-
-        Ok(reading)
+    /// Reads the voltage of a button output
+    fn read_channel(&mut self, channel: usize) -> Result<u16, EspError> {
+        match channel {
+            LEFT_OPEN_CHANNEL => self.adc_driver.read(&mut self.left_pw.open_pin),
+            LEFT_CLOSE_CHANNEL => self.adc_driver.read(&mut self.left_pw.close_pin),
+            RIGHT_OPEN_CHANNEL => self.adc_driver.read(&mut self.right_pw.open_pin),
+            _ => self.adc_driver.read(&mut self.right_pw.close_pin),
+        }
     }
 }
 
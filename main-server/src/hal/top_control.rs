@@ -0,0 +1,37 @@
+use esp_idf_hal::gpio::{Gpio18, Gpio19, Input, Output, PinDriver, Pull};
+use esp_idf_sys::EspError;
+
+/// Top switch of the driver and the relay letting the convertible top move
+pub struct TopControl {
+    switch_pin: PinDriver<'static, Gpio18, Input>,
+    release_pin: PinDriver<'static, Gpio19, Output>,
+}
+
+impl TopControl {
+    /// Prepares the pins with the top held
+    pub fn new(switch_pin: Gpio18, release_pin: Gpio19) -> Result<TopControl, EspError> {
+        let mut switch_pin = PinDriver::input(switch_pin)?;
+        switch_pin.set_pull(Pull::Up)?;
+
+        let mut release_pin = PinDriver::output(release_pin)?;
+        release_pin.set_low()?;
+
+        Ok(TopControl {
+            switch_pin,
+            release_pin,
+        })
+    }
+
+    /// Whether the driver holds the top switch, it pulls the input low
+    pub fn is_top_switch_pressed(&self) -> bool {
+        self.switch_pin.is_low()
+    }
+
+    /// Lets the top move or keeps it from moving
+    pub fn set_top_released(&mut self, released: bool) -> Result<(), EspError> {
+        match released {
+            true => self.release_pin.set_high(),
+            false => self.release_pin.set_low(),
+        }
+    }
+}
@@ -0,0 +1,7 @@
+pub mod app;
+#[cfg(feature = "esp")]
+pub mod bt;
+pub mod clients;
+pub mod hal;
+#[cfg(feature = "esp")]
+pub mod svc;
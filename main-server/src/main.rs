@@ -1,38 +1,41 @@
 use std::sync::Arc;
 
-use bt::server::BluetoothServer;
-use clients::list::ClientsList;
 use esp_idf_hal::peripherals::Peripherals;
-use hal::power_window_controls_driver::PowerWindowDriver;
-use hal::{
+use esp_idf_svc::nvs::EspDefaultNvsPartition;
+use main_server::bt::server::BluetoothServer;
+use main_server::clients::list::ClientsList;
+use main_server::clients::types::ClientType;
+use main_server::hal::lockout_button::LockoutButton;
+use main_server::hal::power_window_controls_driver::PowerWindowDriver;
+use main_server::hal::top_control::TopControl;
+use main_server::hal::{
     DefaultLeftRequiredButtonPins, DefaultPowerWindowPeripherals, DefaultRightRequiredButtonPins,
 };
-use shared_lib::dto::pw_config::PowerWindowsConfig;
+use main_server::svc::clients::ClientsSvc;
+use main_server::svc::passenger_lockout::PassengerLockoutSvc;
+use main_server::svc::power_window::PowerWindowsSvc;
+use main_server::svc::rest_client::RestClientSvc;
+use main_server::svc::top_sequence::TopSequenceSvc;
+use shared_lib::dto::pw_config::{PowerWindowsConfig, DTO_SIZE};
 use shared_lib::system::{run_tokio_runtime, setup_system};
 use shared_lib::wifi::config::{SYSTEM_AP_PASSWORD, SYSTEM_AP_SSID};
 use shared_lib::wifi::server::create_wifi_ap_sync;
-use svc::clients::ClientsSvc;
-use svc::power_window::PowerWindowsSvc;
-use svc::rest_client::RestClientSvc;
 use tokio::join;
 use tokio::sync::{broadcast, Mutex};
 
-use crate::clients::types::ClientType;
-
-mod app;
-mod bt;
-mod clients;
-mod hal;
-mod svc;
+/// Whether the windows go back up once the top has moved
+const RAISE_WINDOWS_AFTER_TOP: bool = true;
 
 fn main() -> anyhow::Result<()> {
     setup_system()?;
 
     let peripherals = Peripherals::take().expect("Couldn't take peripherals");
 
+    let nvs = EspDefaultNvsPartition::take()?;
+
     // Setup WI-FI AP and client connection
     // let a = create_wifi_ap_sync(peripherals.modem).expect("Setting up custom wifi AP failed");
-    let wifi = create_wifi_ap_sync(peripherals.modem, SYSTEM_AP_SSID, SYSTEM_AP_PASSWORD)
+    let wifi = create_wifi_ap_sync(peripherals.modem, nvs.clone(), SYSTEM_AP_SSID, SYSTEM_AP_PASSWORD)
         .expect("Failed to connect to wifi...");
 
     let bt_server = BluetoothServer::new().expect("Failed to create BT server...");
@@ -53,12 +56,27 @@ fn main() -> anyhow::Result<()> {
         },
     )?));
 
+    let top_control = TopControl::new(peripherals.pins.gpio18, peripherals.pins.gpio19)?;
+
+    // The lockout is restored before any door is told about it
+    let passenger_lockout_svc = PassengerLockoutSvc::new(nvs)?;
+    let lockout_button = LockoutButton::new(peripherals.pins.gpio21)?;
+
     let rest_svc = Arc::new(Mutex::new(RestClientSvc::new()));
 
     run_tokio_runtime(async move {
         let (clients_sender, clients_receiver) = broadcast::channel::<ClientsList>(8);
-        let (http_sender, http_receiver) = broadcast::channel::<(ClientType, &'static str, [u8; 8])>(8);
+        let (http_sender, http_receiver) = broadcast::channel::<(ClientType, &'static str, [u8; DTO_SIZE])>(8);
         let (pw_cfg_sender, pw_cfg_receiver) = broadcast::channel::<PowerWindowsConfig>(8);
+        let (lockout_sender, lockout_requests_receiver) = broadcast::channel::<bool>(8);
+        let lockout_receiver = passenger_lockout_svc.subscribe();
+
+        let top_sequence_task = TopSequenceSvc::run_loop(
+            top_control,
+            clients_sender.subscribe(),
+            http_sender.clone(),
+            RAISE_WINDOWS_AFTER_TOP,
+        );
 
         let clients_svc_task = ClientsSvc::run_loop(wifi, clients_sender, clients_svc);
 
@@ -67,11 +85,15 @@ fn main() -> anyhow::Result<()> {
             http_sender
         );
 
-        bt_server.setup(pw_cfg_sender);
+        bt_server.setup(pw_cfg_sender, lockout_sender, lockout_receiver.clone());
+
+        let lockout_svc_task =
+            PassengerLockoutSvc::run_loop(passenger_lockout_svc, lockout_button, lockout_requests_receiver);
 
-        let rest_svc_task = RestClientSvc::run_loop(clients_receiver, pw_cfg_receiver, http_receiver, rest_svc);
+        let rest_svc_task =
+            RestClientSvc::run_loop(clients_receiver, pw_cfg_receiver, http_receiver, lockout_receiver, rest_svc);
 
-        join!(clients_svc_task, pw_svc_task, rest_svc_task);
+        join!(clients_svc_task, pw_svc_task, rest_svc_task, top_sequence_task, lockout_svc_task);
     })?;
 
     Ok(())
@@ -1,3 +1,5 @@
 pub mod clients;
+pub mod passenger_lockout;
 pub mod power_window;
-pub mod rest_client;
\ No newline at end of file
+pub mod rest_client;
+pub mod top_sequence;
\ No newline at end of file
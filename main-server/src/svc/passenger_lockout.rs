@@ -0,0 +1,90 @@
+use std::time::{Duration, Instant};
+
+use esp_idf_svc::nvs::{EspDefaultNvsPartition, EspNvs, NvsDefault};
+use esp_idf_sys::EspError;
+use tokio::sync::{broadcast, watch};
+
+use crate::{app::passenger_lockout::PassengerLockout, hal::lockout_button::LockoutButton};
+
+const NVS_NAMESPACE: &str = "main_server";
+const LOCKOUT_KEY: &str = "pw_lockout";
+
+/// How often the console button is read
+const BUTTON_POLL_INTERVAL: Duration = Duration::from_millis(20);
+
+pub struct PassengerLockoutSvc {
+    nvs: EspNvs<NvsDefault>,
+    lockout: PassengerLockout,
+    lockout_sender: watch::Sender<bool>,
+}
+
+impl PassengerLockoutSvc {
+    /// Restores the persisted lockout, the passenger switches work if there is none
+    pub fn new(partition: EspDefaultNvsPartition) -> Result<PassengerLockoutSvc, EspError> {
+        let nvs = EspNvs::new(partition, NVS_NAMESPACE, true)?;
+
+        let locked = match nvs.get_u8(LOCKOUT_KEY) {
+            Ok(locked) => locked.is_some_and(|locked| locked != 0),
+            Err(err) => {
+                log::error!("Couldn't load passenger lockout: {:?}", err);
+                false
+            }
+        };
+
+        log::info!("Passenger lockout {}.", if locked { "engaged" } else { "released" });
+
+        Ok(PassengerLockoutSvc {
+            nvs,
+            lockout: PassengerLockout::new(locked),
+            lockout_sender: watch::channel(locked).0,
+        })
+    }
+
+    /// Receives the lockout whenever it changes
+    pub fn subscribe(&self) -> watch::Receiver<bool> {
+        self.lockout_sender.subscribe()
+    }
+
+    /// Toggles the lockout with every press of `button` and sets it as asked for by `requests_receiver`
+    pub async fn run_loop(
+        mut svc: PassengerLockoutSvc,
+        button: LockoutButton,
+        mut requests_receiver: broadcast::Receiver<bool>,
+    ) {
+        log::info!("Spawned passenger lockout service.");
+
+        let mut interval = tokio::time::interval(BUTTON_POLL_INTERVAL);
+        let started = Instant::now();
+
+        loop {
+            let changed = tokio::select! {
+                request = requests_receiver.recv() => match request {
+                    Ok(locked) => svc.lockout.set(locked),
+                    Err(broadcast::error::RecvError::Closed) => panic!("Event channel closed!"),
+                    Err(broadcast::error::RecvError::Lagged(count)) => {
+                        log::warn!("Event channel lagged by {} events, skipping...", count);
+                        continue;
+                    }
+                },
+                _ = interval.tick() => svc.lockout.update_button(button.is_pressed(), started.elapsed().as_millis()),
+            };
+
+            if changed {
+                svc.publish();
+            }
+        }
+    }
+
+    /// Persists the lockout and passes it on to the doors
+    fn publish(&mut self) {
+        let locked = self.lockout.is_locked();
+
+        log::info!("Passenger lockout {}.", if locked { "engaged" } else { "released" });
+
+        if let Err(err) = self.nvs.set_u8(LOCKOUT_KEY, locked as u8) {
+            log::error!("Couldn't persist passenger lockout: {:?}", err);
+        }
+
+        self.lockout_sender.send_replace(locked);
+    }
+}
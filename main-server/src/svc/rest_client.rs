@@ -1,14 +1,36 @@
-use std::sync::Arc;
+use std::{
+    sync::Arc,
+    time::{Duration, Instant},
+};
 
 use embedded_svc::http::client::Client;
-use esp_idf_svc::http::client::EspHttpConnection;
-use shared_lib::{dto::pw_config::{PowerWindowsConfig, Serialize}, http::endpoints};
+use esp_idf_svc::http::client::{Configuration, EspHttpConnection};
+use shared_lib::{
+    dto::{
+        pw_config::{PowerWindowsConfig, Serialize, DTO_SIZE},
+        pw_lockout::PassengerLockoutState,
+    },
+    http::endpoints,
+};
 use tokio::{
     join,
-    sync::{broadcast, Mutex},
+    sync::{broadcast, watch, Mutex},
+};
+
+use crate::clients::{
+    list::ClientsList,
+    types::{ClientType, CLIENT_TYPES},
 };
 
-use crate::clients::{list::ClientsList, types::ClientType};
+/// How often the door modules are told the main server is alive, well within their link loss timeout.
+/// The heartbeats carry the passenger lockout, doors which have just booted pick it up right away.
+const HEARTBEAT_INTERVAL: Duration = Duration::from_millis(250);
+/// How long a heartbeat may take, a door which doesn't answer in time misses only its own heartbeats
+const HEARTBEAT_TIMEOUT: Duration = Duration::from_millis(300);
+/// Stack of the threads sending the heartbeats, the HTTP client needs more than the default
+const HEARTBEAT_STACK_SIZE: usize = 8192;
+/// How long a request to a door may take, one which doesn't answer mustn't hold up the runtime
+const REQUEST_TIMEOUT: Duration = Duration::from_millis(150);
 
 #[derive(Debug, Clone)]
 pub struct RestClientSvc {
@@ -25,9 +47,12 @@ impl RestClientSvc {
     pub async fn run_loop(
         mut clients_receiver: broadcast::Receiver<ClientsList>,
         mut pw_cfg_receiver: broadcast::Receiver<PowerWindowsConfig>,
-        mut http_receiver: broadcast::Receiver<(ClientType, &'static str, [u8; 8])>,
+        mut http_receiver: broadcast::Receiver<(ClientType, &'static str, [u8; DTO_SIZE])>,
+        lockout_receiver: watch::Receiver<bool>,
         svc_src: Arc<Mutex<Self>>,
     ) {
+        let (clients_sender, clients_watch) = watch::channel(ClientsList::default());
+
         let svc = svc_src.clone();
         let clients_listener_task = tokio::spawn(async move {
             loop {
@@ -42,6 +67,8 @@ impl RestClientSvc {
                     },
                 };
 
+                clients_sender.send_replace(new_client_list);
+
                 let mut svc = svc.lock().await;
                 svc.clients = new_client_list;
             }
@@ -64,24 +91,17 @@ impl RestClientSvc {
                     },
                 };
 
-                let svc = svc.lock().await;
+                let clients = svc.lock().await.clients;
 
                 let pw_cfg_raw = pw_cfg.serialize();
-                Self::call_for_client(
-                    svc.clients,
-                    ClientType::LeftDoor,
-                    endpoints::CONFIGURE_WINDOWS_CURRENT_THRESHOLDS_PATH,
-                    pw_cfg_raw,
-                )
-                .unwrap();
-                
-                Self::call_for_client(
-                    svc.clients,
-                    ClientType::RightDoor,
-                    endpoints::CONFIGURE_WINDOWS_CURRENT_THRESHOLDS_PATH,
-                    pw_cfg_raw,
-                )
-                .unwrap();
+                for client_type in CLIENT_TYPES {
+                    Self::send_to_client(
+                        clients,
+                        client_type,
+                        endpoints::CONFIGURE_WINDOWS_CURRENT_THRESHOLDS_PATH,
+                        pw_cfg_raw,
+                    );
+                }
             }
         });
 
@@ -102,11 +122,24 @@ impl RestClientSvc {
                     },
                 };
 
-                let svc = svc.lock().await;
-                Self::call_for_client(svc.clients, client_type, endpoint, buffer).unwrap();
+                let clients = svc.lock().await.clients;
+                Self::send_to_client(clients, client_type, endpoint, buffer);
             }
         });
 
+        // Sent off the runtime, a door which doesn't answer mustn't hold up anything else
+        for client_type in CLIENT_TYPES {
+            let clients_receiver = clients_watch.clone();
+            let lockout_receiver = lockout_receiver.clone();
+
+            std::thread::Builder::new()
+                .stack_size(HEARTBEAT_STACK_SIZE)
+                .spawn(move || {
+                    Self::send_heartbeats(client_type, clients_receiver, lockout_receiver)
+                })
+                .expect("Couldn't spawn heartbeat thread");
+        }
+
         join!(
             clients_listener_task,
             pw_cfg_listener_task,
@@ -114,8 +147,70 @@ impl RestClientSvc {
         );
     }
 
-    fn get_client() -> anyhow::Result<Client<EspHttpConnection>> {
-        Ok(Client::wrap(EspHttpConnection::new(&Default::default())?))
+    /// Tells a single door the main server is alive, blocking the calling thread for good.
+    /// The door gets a connection of its own, which is opened again after a failed heartbeat.
+    fn send_heartbeats(
+        client_type: ClientType,
+        clients_receiver: watch::Receiver<ClientsList>,
+        lockout_receiver: watch::Receiver<bool>,
+    ) {
+        log::info!("Spawned heartbeat thread for {:?}.", client_type);
+
+        let mut client = None;
+
+        loop {
+            let started = Instant::now();
+            let clients = *clients_receiver.borrow();
+
+            // Doors which aren't connected can't be reached anyway
+            if clients.get_client_for_type(client_type).is_some() {
+                let heartbeat = PassengerLockoutState {
+                    locked: *lockout_receiver.borrow(),
+                }
+                .serialize();
+
+                if let Err(err) = Self::send_heartbeat(&mut client, clients, client_type, heartbeat)
+                {
+                    log::warn!("Couldn't send heartbeat to {:?}: {:?}", client_type, err);
+                    client = None;
+                }
+            }
+
+            std::thread::sleep(HEARTBEAT_INTERVAL.saturating_sub(started.elapsed()));
+        }
+    }
+
+    fn send_heartbeat(
+        client: &mut Option<Client<EspHttpConnection>>,
+        clients: ClientsList,
+        client_type: ClientType,
+        heartbeat: [u8; DTO_SIZE],
+    ) -> anyhow::Result<()> {
+        let endpoint_url = match Self::get_url(clients, client_type, endpoints::HEARTBEAT_PATH) {
+            Some(url) => url,
+            None => return Err(anyhow::anyhow!("Couldn't get client URL")),
+        };
+
+        if client.is_none() {
+            *client = Some(Self::get_client_with_timeout(HEARTBEAT_TIMEOUT)?);
+        }
+
+        let mut request = client
+            .as_mut()
+            .expect("Heartbeat client just opened")
+            .post(&endpoint_url, &[])?;
+        request.write(&heartbeat)?;
+        request.submit()?;
+
+        Ok(())
+    }
+
+    /// Client giving up on requests which take longer than `timeout`
+    fn get_client_with_timeout(timeout: Duration) -> anyhow::Result<Client<EspHttpConnection>> {
+        Ok(Client::wrap(EspHttpConnection::new(&Configuration {
+            timeout: Some(timeout),
+            ..Default::default()
+        })?))
     }
 
     fn get_url(
@@ -134,27 +229,62 @@ impl RestClientSvc {
         };
     }
 
-    fn call_for_client(
+    /// Reads a DTO from the given client, giving up once the request takes longer than `timeout`
+    pub fn get_for_client(
         clients: ClientsList,
         client_type: ClientType,
         endpoint: &'static str,
-        buffer: [u8; 8],
-    ) -> anyhow::Result<()> {
+        timeout: Duration,
+    ) -> anyhow::Result<[u8; DTO_SIZE]> {
         let endpoint_url = match Self::get_url(clients, client_type, endpoint) {
             Some(url) => url,
             None => return Err(anyhow::anyhow!("Couldn't get client URL")),
         };
 
-        match Self::get_client().unwrap().post(&endpoint_url, &[]) {
-            Ok(mut req) => {
-                req.write(&buffer).unwrap();
-                req.submit().unwrap();
-            }
-            Err(err) => {
-                log::error!("Couldn't send request to client: {:?}", err);
+        let mut client = Self::get_client_with_timeout(timeout)?;
+        let mut response = client.get(&endpoint_url)?.submit()?;
+
+        let mut buffer: [u8; DTO_SIZE] = [0; DTO_SIZE];
+        let mut length = 0;
+
+        while length < DTO_SIZE {
+            match response.read(&mut buffer[length..])? {
+                0 => break,
+                read => length += read,
             }
+        }
+
+        Ok(buffer)
+    }
+
+    /// Posts a DTO to the given client, a door which can't be reached is only logged
+    fn send_to_client(
+        clients: ClientsList,
+        client_type: ClientType,
+        endpoint: &'static str,
+        buffer: [u8; DTO_SIZE],
+    ) {
+        if let Err(err) = Self::call_for_client(clients, client_type, endpoint, buffer) {
+            log::error!("Couldn't send {} to {:?}: {:?}", endpoint, client_type, err);
+        }
+    }
+
+    fn call_for_client(
+        clients: ClientsList,
+        client_type: ClientType,
+        endpoint: &'static str,
+        buffer: [u8; DTO_SIZE],
+    ) -> anyhow::Result<()> {
+        let endpoint_url = match Self::get_url(clients, client_type, endpoint) {
+            Some(url) => url,
+            None => return Err(anyhow::anyhow!("Couldn't get client URL")),
         };
 
+        let mut client = Self::get_client_with_timeout(REQUEST_TIMEOUT)?;
+        let mut request = client.post(&endpoint_url, &[])?;
+        request.write(&buffer)?;
+        request.submit()?;
+
         Ok(())
     }
 }
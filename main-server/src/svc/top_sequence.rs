@@ -0,0 +1,155 @@
+use std::time::{Duration, Instant};
+
+use shared_lib::{
+    dto::{
+        pw_config::{Deserialize, DTO_SIZE},
+        pw_status::PowerWindowStatus,
+    },
+    http::endpoints,
+};
+use tokio::{sync::broadcast, time::MissedTickBehavior};
+
+use crate::{
+    app::top_sequence::{DoorReport, TopSequence, TopSequenceCommand},
+    clients::{
+        addresses::get_mac_for_client_type,
+        list::ClientsList,
+        types::{ClientType, CLIENT_TYPES},
+    },
+    hal::top_control::TopControl,
+};
+
+use super::rest_client::RestClientSvc;
+
+/// How often the top switch is read and the doors are polled while the sequence runs
+const TOP_SEQUENCE_INTERVAL: Duration = Duration::from_millis(200);
+/// How long a door may take to report its status before it counts as offline
+const DOOR_STATUS_TIMEOUT: Duration = Duration::from_millis(150);
+
+pub struct TopSequenceSvc {}
+
+impl TopSequenceSvc {
+    /// Runs the top sequence, every door with a known address takes part in it.
+    /// The doors are commanded through `http_sender` and polled for their status directly.
+    pub async fn run_loop(
+        mut top_control: TopControl,
+        mut clients_receiver: broadcast::Receiver<ClientsList>,
+        http_sender: broadcast::Sender<(ClientType, &'static str, [u8; DTO_SIZE])>,
+        raise_windows_afterwards: bool,
+    ) {
+        log::info!("Spawned top sequence service.");
+
+        let doors = CLIENT_TYPES
+            .into_iter()
+            .filter(|client_type| get_mac_for_client_type(*client_type).is_some())
+            .collect();
+
+        let mut sequence = TopSequence::new(doors, raise_windows_afterwards);
+        let mut clients = ClientsList::default();
+        let mut interval = tokio::time::interval(TOP_SEQUENCE_INTERVAL);
+        interval.set_missed_tick_behavior(MissedTickBehavior::Delay);
+        let started = Instant::now();
+
+        loop {
+            tokio::select! {
+                new_client_list = clients_receiver.recv() => match new_client_list {
+                    Ok(new_client_list) => clients = new_client_list,
+                    Err(broadcast::error::RecvError::Closed) => panic!("Event channel closed!"),
+                    Err(broadcast::error::RecvError::Lagged(count)) => {
+                        log::warn!("Event channel lagged by {} events, skipping...", count);
+                    }
+                },
+                _ = interval.tick() => {
+                    let reports = match sequence.is_running() {
+                        true => Self::read_doors(clients, sequence.doors()).await,
+                        false => Vec::new(),
+                    };
+
+                    let commands = sequence.update(
+                        top_control.is_top_switch_pressed(),
+                        &reports,
+                        started.elapsed().as_millis(),
+                    );
+
+                    for command in commands {
+                        Self::execute(command, &sequence, clients, &mut top_control, &http_sender);
+                    }
+                }
+            }
+        }
+    }
+
+    /// Polls all doors at once off the runtime, a door which doesn't answer in time is offline
+    async fn read_doors(clients: ClientsList, doors: &[ClientType]) -> Vec<DoorReport> {
+        let polls: Vec<_> = doors
+            .iter()
+            .map(|door| {
+                let door = *door;
+                tokio::task::spawn_blocking(move || Self::read_door(clients, door))
+            })
+            .collect();
+
+        let mut reports = Vec::with_capacity(polls.len());
+
+        for poll in polls {
+            reports.push(poll.await.unwrap_or_else(|err| {
+                log::error!("Door status poll failed: {:?}", err);
+                DoorReport::Offline
+            }));
+        }
+
+        reports
+    }
+
+    fn read_door(clients: ClientsList, client_type: ClientType) -> DoorReport {
+        match RestClientSvc::get_for_client(
+            clients,
+            client_type,
+            endpoints::WINDOWS_STATUS_PATH,
+            DOOR_STATUS_TIMEOUT,
+        ) {
+            Ok(buffer) => DoorReport::Status(PowerWindowStatus::deserialize(buffer)),
+            Err(err) => {
+                log::warn!("Couldn't read the status of {:?}: {:?}", client_type, err);
+                DoorReport::Offline
+            }
+        }
+    }
+
+    fn execute(
+        command: TopSequenceCommand,
+        sequence: &TopSequence,
+        clients: ClientsList,
+        top_control: &mut TopControl,
+        http_sender: &broadcast::Sender<(ClientType, &'static str, [u8; DTO_SIZE])>,
+    ) {
+        let requests: Vec<(ClientType, &'static str)> = match command {
+            TopSequenceCommand::LowerWindow(client_type) => vec![(client_type, endpoints::OPEN_WINDOWS_FULLY_PATH)],
+            TopSequenceCommand::RaiseWindow(client_type) => vec![(client_type, endpoints::CLOSE_WINDOWS_FULLY_PATH)],
+            TopSequenceCommand::StopWindows => sequence
+                .doors()
+                .iter()
+                .map(|client_type| (*client_type, endpoints::STOP_WINDOWS_PATH))
+                .collect(),
+            TopSequenceCommand::ReleaseTop | TopSequenceCommand::HoldTop => {
+                let released = command == TopSequenceCommand::ReleaseTop;
+
+                if let Err(err) = top_control.set_top_released(released) {
+                    log::error!("Couldn't switch the top release to {}: {:?}", released, err);
+                }
+
+                return;
+            }
+        };
+
+        // Doors which aren't connected can't be reached anyway
+        for (client_type, endpoint) in requests
+            .into_iter()
+            .filter(|(client_type, _)| clients.get_client_for_type(*client_type).is_some())
+        {
+            if let Err(err) = http_sender.send((client_type, endpoint, [0; DTO_SIZE])) {
+                log::error!("Couldn't send {} to {:?}: {:?}", endpoint, client_type, err);
+            }
+        }
+    }
+}
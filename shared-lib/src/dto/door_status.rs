@@ -0,0 +1,53 @@
+use serde::{Deserialize, Serialize};
+
+use crate::units::Milliamps;
+
+use super::pw_config::PowerWindowsConfig;
+
+/// Everything the door module knows about itself, served as JSON
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct DoorStatusReport {
+    pub firmware_version: String,
+    pub uptime_millis: u64,
+    /// Name of the window state
+    pub state: String,
+    /// Raw value of the window state, as in the binary status
+    pub state_code: u8,
+    /// Estimated position in percent, 0 is fully closed, 100 is fully open
+    pub position_percent: Option<u8>,
+    /// Filtered motor currents as last read by the control loop
+    pub currents: CurrentReport,
+    /// Name of the cause for the last motor stop
+    pub last_stop_cause: Option<String>,
+    /// Config the window is run with right now, including calibrated thresholds
+    pub config: PowerWindowsConfig,
+    pub faults: Vec<FaultReport>,
+    /// Whether the main server's heartbeats have stopped
+    pub link_lost: bool,
+    /// Uptime at which the door module last entered link-loss mode
+    pub link_lost_at_uptime_millis: Option<u64>,
+    /// Name of where the last movement has been asked for
+    pub movement_source: Option<String>,
+    /// Whether the main server has locked out the window switch on the door
+    pub passenger_lockout: bool,
+}
+
+#[derive(Debug, Clone, Copy, Default, Serialize, Deserialize)]
+pub struct CurrentReport {
+    pub closing_milliamps: Milliamps,
+    pub opening_milliamps: Milliamps,
+    /// Either current sense voltage was at the end of the ADC range
+    pub saturated: bool,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct FaultReport {
+    /// Name of the fault code
+    pub code: String,
+    /// Raw value of the fault code, as in the binary fault list
+    pub code_value: u8,
+    pub severity: String,
+    /// Whether the fault is active or only stored
+    pub status: String,
+    pub occurrences: u16,
+}
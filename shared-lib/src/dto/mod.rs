@@ -1 +1,8 @@
-pub mod pw_config;
\ No newline at end of file
+pub mod door_status;
+pub mod pw_calibration;
+pub mod pw_config;
+pub mod pw_faults;
+pub mod pw_lockout;
+pub mod pw_position;
+pub mod pw_status;
+pub mod telemetry;
\ No newline at end of file
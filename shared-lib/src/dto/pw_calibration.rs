@@ -0,0 +1,60 @@
+use crate::units::{Milliamps, Milliseconds};
+
+use super::pw_config::{Deserialize, PowerWindowsConfig, Serialize, DTO_SIZE};
+
+/// Window properties measured in place by the door module's calibration routine
+#[derive(Debug, Clone, Copy, Default)]
+pub struct PowerWindowCalibration {
+    /// Time the glass needs to travel from fully closed to fully open
+    pub opening_travel_time: Milliseconds,
+    /// Time the glass needs to travel from fully open to fully closed
+    pub closing_travel_time: Milliseconds,
+    /// Current drawn while the glass travels freely down
+    pub opening_running_current: Milliamps,
+    /// Current drawn while the glass travels freely up
+    pub closing_running_current: Milliamps,
+    /// Current drawn while the motor stalls against the lower end stop
+    pub opening_stall_current: Milliamps,
+    /// Current drawn while the motor stalls against the upper end stop
+    pub closing_stall_current: Milliamps,
+}
+
+impl PowerWindowCalibration {
+    /// Puts the interrupt thresholds halfway between the running and the stall current,
+    /// the remaining values of `config` are kept
+    pub fn apply_to(&self, config: PowerWindowsConfig) -> PowerWindowsConfig {
+        PowerWindowsConfig {
+            opening_current_interrupt_threshold: self.opening_running_current.midpoint(self.opening_stall_current),
+            closing_current_interrupt_threshold: self.closing_running_current.midpoint(self.closing_stall_current),
+            ..config
+        }
+    }
+}
+
+impl Serialize for PowerWindowCalibration {
+    fn serialize(&self) -> [u8; DTO_SIZE] {
+        let mut buffer: [u8; DTO_SIZE] = [0; DTO_SIZE];
+
+        buffer[0..2].copy_from_slice(&self.opening_travel_time.to_be_bytes());
+        buffer[2..4].copy_from_slice(&self.closing_travel_time.to_be_bytes());
+        buffer[4..6].copy_from_slice(&self.opening_running_current.to_be_bytes());
+        buffer[6..8].copy_from_slice(&self.closing_running_current.to_be_bytes());
+        buffer[8..10].copy_from_slice(&self.opening_stall_current.to_be_bytes());
+        buffer[10..12].copy_from_slice(&self.closing_stall_current.to_be_bytes());
+
+        buffer
+    }
+}
+
+impl Deserialize for PowerWindowCalibration {
+    fn deserialize(buffer: [u8; DTO_SIZE]) -> Self {
+        PowerWindowCalibration {
+            opening_travel_time: Milliseconds::from_be_bytes([buffer[0], buffer[1]]),
+            closing_travel_time: Milliseconds::from_be_bytes([buffer[2], buffer[3]]),
+            opening_running_current: Milliamps::from_be_bytes([buffer[4], buffer[5]]),
+            closing_running_current: Milliamps::from_be_bytes([buffer[6], buffer[7]]),
+            opening_stall_current: Milliamps::from_be_bytes([buffer[8], buffer[9]]),
+            closing_stall_current: Milliamps::from_be_bytes([buffer[10], buffer[11]]),
+        }
+    }
+}
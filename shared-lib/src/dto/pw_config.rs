@@ -1,40 +1,241 @@
+use crate::units::{Milliamps, Milliseconds, Millivolts, MillivoltsPerAmp};
+
+/// Size of every DTO on the wire, shorter payloads are zero padded
+pub const DTO_SIZE: usize = 32;
+
 pub trait Serialize {
-    fn serialize(&self) -> [u8; 8];
+    fn serialize(&self) -> [u8; DTO_SIZE];
 }
 
 pub trait Deserialize {
-    fn deserialize(buffer: [u8; 8]) -> Self;
+    fn deserialize(buffer: [u8; DTO_SIZE]) -> Self;
+}
+
+/// Interrupt threshold used until the window has been calibrated, measurable with the default current sense
+pub const DEFAULT_CURRENT_INTERRUPT_THRESHOLD: Milliamps = Milliamps(8_000);
+pub const DEFAULT_HANDLE_TIME_THRESHOLD: Milliseconds = Milliseconds(300);
+pub const DEFAULT_OBSTRUCTION_REVERSE_TIME: Milliseconds = Milliseconds(600);
+pub const DEFAULT_OBSTRUCTION_REVERSE_PERCENT: u8 = 15;
+pub const DEFAULT_INRUSH_BLANKING_TIME: Milliseconds = Milliseconds(250);
+/// Just below the about 9.3A the default current sense saturates at
+pub const DEFAULT_INRUSH_CURRENT_LIMIT: Milliamps = Milliamps(9_000);
+pub const DEFAULT_THERMAL_LIMIT_AMP2_SECONDS: u16 = 1500;
+pub const DEFAULT_THERMAL_COOLING_TIME_CONSTANT_SECS: u16 = 300;
+/// Gain of the current sense amplifiers on the door module
+pub const DEFAULT_SENSE_GAIN: MillivoltsPerAmp = MillivoltsPerAmp(80);
+/// Upper end of the ADC range without attenuation
+pub const DEFAULT_SENSE_SATURATION: Millivolts = Millivolts(750);
+pub const DEFAULT_SENSE_ZERO_SETTLE_TIME: Milliseconds = Milliseconds(1000);
+pub const DEFAULT_LINK_LOSS_TIMEOUT: Milliseconds = Milliseconds(1000);
+/// About 1 cm of glass travel on the E36 coupe
+pub const DEFAULT_SHORT_DROP_TIME: Milliseconds = Milliseconds(120);
+
+/// What a door module does once it stops hearing heartbeats from the main server
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Hash, serde::Serialize, serde::Deserialize)]
+pub enum LinkLossPolicy {
+    /// Stops every movement right away, apart from the reversal after an obstruction
+    #[default]
+    Stop = 1,
+    /// Lets full and targeted movements run to their end, continuous ones stop
+    FinishMovement = 2,
+    /// Closes the window completely
+    CloseFully = 3,
+}
+
+impl LinkLossPolicy {
+    pub const ALL: [LinkLossPolicy; 3] = [LinkLossPolicy::Stop, LinkLossPolicy::FinishMovement, LinkLossPolicy::CloseFully];
+
+    pub fn from_raw(raw: u8) -> Option<LinkLossPolicy> {
+        LinkLossPolicy::ALL.into_iter().find(|policy| *policy as u8 == raw)
+    }
 }
 
-#[derive(Debug, Clone, Copy)]
+#[derive(Debug, Clone, Copy, serde::Serialize, serde::Deserialize)]
 pub struct PowerWindowsConfig {
-    pub opening_current_interrupt_threshold_amps: u16,
-    pub closing_current_interrupt_threshold_amps: u16,
-    pub handle_time_threshold_millis: u16,
+    pub opening_current_interrupt_threshold: Milliamps,
+    pub closing_current_interrupt_threshold: Milliamps,
+    pub handle_time_threshold: Milliseconds,
+    /// How long to drive down after an obstruction while closing, used when the position is unknown
+    pub obstruction_reverse_time: Milliseconds,
+    /// How far to drive down after an obstruction while closing, in percent of the full travel
+    pub obstruction_reverse_percent: u8,
+    /// How long after a relay change the inrush limit applies instead of the interrupt thresholds
+    pub inrush_blanking_time: Milliseconds,
+    /// Current which interrupts the motor during the inrush blanking window
+    pub inrush_current_limit: Milliamps,
+    /// Accumulated I²t of the motor at which new movements are refused, in A²s
+    pub thermal_limit_amp2_seconds: u16,
+    /// Time constant the motor cools down with, in seconds
+    pub thermal_cooling_time_constant_secs: u16,
+    /// Motor current ripples over the full travel, 0 disables ripple counting
+    pub ripples_per_travel: u16,
+    /// Gain of the closing current sense amplifier
+    pub closing_sense_gain: MillivoltsPerAmp,
+    /// Gain of the opening current sense amplifier
+    pub opening_sense_gain: MillivoltsPerAmp,
+    /// Sense voltage from which on the ADC saturates
+    pub sense_saturation: Millivolts,
+    /// How long both relays have to be off before the zero current offset is measured
+    pub sense_zero_settle_time: Milliseconds,
+    /// What the door module does once the main server's heartbeats stop
+    pub link_loss_policy: LinkLossPolicy,
+    /// How long without a heartbeat until the link to the main server counts as lost
+    pub link_loss_timeout: Milliseconds,
+    /// How long frameless glass drops off the seal when the door is opened
+    pub short_drop_time: Milliseconds,
+}
+
+impl Default for PowerWindowsConfig {
+    fn default() -> Self {
+        PowerWindowsConfig {
+            opening_current_interrupt_threshold: DEFAULT_CURRENT_INTERRUPT_THRESHOLD,
+            closing_current_interrupt_threshold: DEFAULT_CURRENT_INTERRUPT_THRESHOLD,
+            handle_time_threshold: DEFAULT_HANDLE_TIME_THRESHOLD,
+            obstruction_reverse_time: DEFAULT_OBSTRUCTION_REVERSE_TIME,
+            obstruction_reverse_percent: DEFAULT_OBSTRUCTION_REVERSE_PERCENT,
+            inrush_blanking_time: DEFAULT_INRUSH_BLANKING_TIME,
+            inrush_current_limit: DEFAULT_INRUSH_CURRENT_LIMIT,
+            thermal_limit_amp2_seconds: DEFAULT_THERMAL_LIMIT_AMP2_SECONDS,
+            thermal_cooling_time_constant_secs: DEFAULT_THERMAL_COOLING_TIME_CONSTANT_SECS,
+            ripples_per_travel: 0,
+            closing_sense_gain: DEFAULT_SENSE_GAIN,
+            opening_sense_gain: DEFAULT_SENSE_GAIN,
+            sense_saturation: DEFAULT_SENSE_SATURATION,
+            sense_zero_settle_time: DEFAULT_SENSE_ZERO_SETTLE_TIME,
+            link_loss_policy: LinkLossPolicy::default(),
+            link_loss_timeout: DEFAULT_LINK_LOSS_TIMEOUT,
+            short_drop_time: DEFAULT_SHORT_DROP_TIME,
+        }
+    }
+}
+
+impl PowerWindowsConfig {
+    /// Largest current the sensor tells apart from saturation with the given gain
+    fn max_sensed_current(&self, gain: MillivoltsPerAmp) -> Milliamps {
+        let below_saturation = self.sense_saturation.saturating_sub(Millivolts(1));
+
+        gain.checked_current(below_saturation).unwrap_or(Milliamps::MAX)
+    }
+
+    /// Lowers thresholds the current sense can't report to the largest current it can.
+    /// A threshold above the saturation would never interrupt a movement.
+    pub fn clamped_to_sense_range(mut self) -> PowerWindowsConfig {
+        let max_opening = self.max_sensed_current(self.opening_sense_gain);
+        let max_closing = self.max_sensed_current(self.closing_sense_gain);
+        let max_inrush = max_opening.min(max_closing);
+
+        if self.opening_current_interrupt_threshold > max_opening {
+            log::warn!(
+                "Opening interrupt threshold {} isn't measurable, using {}",
+                self.opening_current_interrupt_threshold,
+                max_opening
+            );
+            self.opening_current_interrupt_threshold = max_opening;
+        }
+
+        if self.closing_current_interrupt_threshold > max_closing {
+            log::warn!(
+                "Closing interrupt threshold {} isn't measurable, using {}",
+                self.closing_current_interrupt_threshold,
+                max_closing
+            );
+            self.closing_current_interrupt_threshold = max_closing;
+        }
+
+        if self.inrush_current_limit > max_inrush {
+            log::warn!("Inrush current limit {} isn't measurable, using {}", self.inrush_current_limit, max_inrush);
+            self.inrush_current_limit = max_inrush;
+        }
+
+        self
+    }
 }
 
 impl Serialize for PowerWindowsConfig {
-    fn serialize(&self) -> [u8; 8] {
-        let mut buffer: [u8; 8] = [0; 8];
+    fn serialize(&self) -> [u8; DTO_SIZE] {
+        let mut buffer: [u8; DTO_SIZE] = [0; DTO_SIZE];
 
-        buffer[0..2].copy_from_slice(&self.opening_current_interrupt_threshold_amps.to_be_bytes());
-        buffer[2..4].copy_from_slice(&self.closing_current_interrupt_threshold_amps.to_be_bytes());
-        buffer[4..6].copy_from_slice(&self.handle_time_threshold_millis.to_be_bytes());
+        buffer[0..2].copy_from_slice(&self.opening_current_interrupt_threshold.to_be_bytes());
+        buffer[2..4].copy_from_slice(&self.closing_current_interrupt_threshold.to_be_bytes());
+        buffer[4..6].copy_from_slice(&self.handle_time_threshold.to_be_bytes());
+        buffer[6..8].copy_from_slice(&self.obstruction_reverse_time.to_be_bytes());
+        buffer[8] = self.obstruction_reverse_percent;
+        buffer[9..11].copy_from_slice(&self.inrush_blanking_time.to_be_bytes());
+        buffer[11..13].copy_from_slice(&self.inrush_current_limit.to_be_bytes());
+        buffer[13..15].copy_from_slice(&self.thermal_limit_amp2_seconds.to_be_bytes());
+        buffer[15..17].copy_from_slice(&self.thermal_cooling_time_constant_secs.to_be_bytes());
+        buffer[17..19].copy_from_slice(&self.ripples_per_travel.to_be_bytes());
+        buffer[19..21].copy_from_slice(&self.closing_sense_gain.to_be_bytes());
+        buffer[21..23].copy_from_slice(&self.opening_sense_gain.to_be_bytes());
+        buffer[23..25].copy_from_slice(&self.sense_saturation.to_be_bytes());
+        buffer[25..27].copy_from_slice(&self.sense_zero_settle_time.to_be_bytes());
+        buffer[27] = self.link_loss_policy as u8;
+        buffer[28..30].copy_from_slice(&self.link_loss_timeout.to_be_bytes());
+        buffer[30..32].copy_from_slice(&self.short_drop_time.to_be_bytes());
 
         buffer
     }
 }
 
 impl Deserialize for PowerWindowsConfig {
-    fn deserialize(buffer: [u8; 8]) -> Self {
-        let opening_current_interrupt_threshold_amps = u16::from_be_bytes([buffer[0], buffer[1]]);
-        let closing_current_interrupt_threshold_amps = u16::from_be_bytes([buffer[2], buffer[3]]);
-        let handle_time_threshold_millis = u16::from_be_bytes([buffer[4], buffer[5]]);
+    fn deserialize(buffer: [u8; DTO_SIZE]) -> Self {
+        let opening_current_interrupt_threshold = Milliamps::from_be_bytes([buffer[0], buffer[1]]);
+        let closing_current_interrupt_threshold = Milliamps::from_be_bytes([buffer[2], buffer[3]]);
+        let handle_time_threshold = Milliseconds::from_be_bytes([buffer[4], buffer[5]]);
 
-        PowerWindowsConfig {
-            opening_current_interrupt_threshold_amps,
-            closing_current_interrupt_threshold_amps,
-            handle_time_threshold_millis,
-        }
+        // Older senders only know the first 6 bytes, zero means "use the default"
+        let obstruction_reverse_time =
+            Milliseconds::from_be_bytes([buffer[6], buffer[7]]).non_zero_or(DEFAULT_OBSTRUCTION_REVERSE_TIME);
+        let obstruction_reverse_percent = match buffer[8] {
+            0 => DEFAULT_OBSTRUCTION_REVERSE_PERCENT,
+            percent => percent,
+        };
+        let inrush_blanking_time =
+            Milliseconds::from_be_bytes([buffer[9], buffer[10]]).non_zero_or(DEFAULT_INRUSH_BLANKING_TIME);
+        let inrush_current_limit =
+            Milliamps::from_be_bytes([buffer[11], buffer[12]]).non_zero_or(DEFAULT_INRUSH_CURRENT_LIMIT);
+        let thermal_limit_amp2_seconds = match u16::from_be_bytes([buffer[13], buffer[14]]) {
+            0 => DEFAULT_THERMAL_LIMIT_AMP2_SECONDS,
+            limit => limit,
+        };
+        let thermal_cooling_time_constant_secs = match u16::from_be_bytes([buffer[15], buffer[16]]) {
+            0 => DEFAULT_THERMAL_COOLING_TIME_CONSTANT_SECS,
+            secs => secs,
+        };
+        let closing_sense_gain =
+            MillivoltsPerAmp::from_be_bytes([buffer[19], buffer[20]]).non_zero_or(DEFAULT_SENSE_GAIN);
+        let opening_sense_gain =
+            MillivoltsPerAmp::from_be_bytes([buffer[21], buffer[22]]).non_zero_or(DEFAULT_SENSE_GAIN);
+        let sense_saturation =
+            Millivolts::from_be_bytes([buffer[23], buffer[24]]).non_zero_or(DEFAULT_SENSE_SATURATION);
+        let sense_zero_settle_time =
+            Milliseconds::from_be_bytes([buffer[25], buffer[26]]).non_zero_or(DEFAULT_SENSE_ZERO_SETTLE_TIME);
+        let link_loss_policy = LinkLossPolicy::from_raw(buffer[27]).unwrap_or_default();
+        let link_loss_timeout =
+            Milliseconds::from_be_bytes([buffer[28], buffer[29]]).non_zero_or(DEFAULT_LINK_LOSS_TIMEOUT);
+        let short_drop_time =
+            Milliseconds::from_be_bytes([buffer[30], buffer[31]]).non_zero_or(DEFAULT_SHORT_DROP_TIME);
+
+        let config = PowerWindowsConfig {
+            opening_current_interrupt_threshold,
+            closing_current_interrupt_threshold,
+            handle_time_threshold,
+            obstruction_reverse_time,
+            obstruction_reverse_percent,
+            inrush_blanking_time,
+            inrush_current_limit,
+            thermal_limit_amp2_seconds,
+            thermal_cooling_time_constant_secs,
+            ripples_per_travel: u16::from_be_bytes([buffer[17], buffer[18]]),
+            closing_sense_gain,
+            opening_sense_gain,
+            sense_saturation,
+            sense_zero_settle_time,
+            link_loss_policy,
+            link_loss_timeout,
+            short_drop_time,
+        };
+
+        config.clamped_to_sense_range()
     }
 }
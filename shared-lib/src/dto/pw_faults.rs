@@ -0,0 +1,80 @@
+use super::pw_config::{Deserialize, Serialize, DTO_SIZE};
+
+/// Bytes a single fault takes up in the serialized list
+const FAULT_SIZE: usize = 4;
+/// Faults which fit into a single DTO, further ones are left out
+pub const MAX_REPORTED_FAULTS: usize = DTO_SIZE / FAULT_SIZE;
+
+#[derive(Debug, Clone, Copy, Default)]
+pub struct PowerWindowFault {
+    /// Raw value of the door module's fault code, never 0
+    pub code: u8,
+    /// Raw value of the fault's severity
+    pub severity: u8,
+    /// Raw value of whether the fault is active or only stored
+    pub status: u8,
+    /// How often the fault has been raised since it was last cleared
+    pub occurrences: u8,
+}
+
+/// Faults the door module currently keeps
+#[derive(Debug, Clone, Default)]
+pub struct PowerWindowFaults {
+    pub faults: Vec<PowerWindowFault>,
+}
+
+impl Serialize for PowerWindowFaults {
+    fn serialize(&self) -> [u8; DTO_SIZE] {
+        let mut buffer: [u8; DTO_SIZE] = [0; DTO_SIZE];
+
+        for (chunk, fault) in buffer.chunks_exact_mut(FAULT_SIZE).zip(&self.faults) {
+            chunk.copy_from_slice(&[fault.code, fault.severity, fault.status, fault.occurrences]);
+        }
+
+        buffer
+    }
+}
+
+impl Deserialize for PowerWindowFaults {
+    fn deserialize(buffer: [u8; DTO_SIZE]) -> Self {
+        let faults = buffer
+            .chunks_exact(FAULT_SIZE)
+            .take_while(|chunk| chunk[0] != 0)
+            .map(|chunk| PowerWindowFault {
+                code: chunk[0],
+                severity: chunk[1],
+                status: chunk[2],
+                occurrences: chunk[3],
+            })
+            .collect();
+
+        PowerWindowFaults { faults }
+    }
+}
+
+#[derive(Debug, Clone, Copy, Default)]
+pub struct ClearFaultRequest {
+    /// Raw value of the fault code to clear, all faults are cleared if there is none
+    pub code: Option<u8>,
+}
+
+impl Serialize for ClearFaultRequest {
+    fn serialize(&self) -> [u8; DTO_SIZE] {
+        let mut buffer: [u8; DTO_SIZE] = [0; DTO_SIZE];
+
+        buffer[0] = self.code.unwrap_or(0);
+
+        buffer
+    }
+}
+
+impl Deserialize for ClearFaultRequest {
+    fn deserialize(buffer: [u8; DTO_SIZE]) -> Self {
+        let code = match buffer[0] {
+            0 => None,
+            code => Some(code),
+        };
+
+        ClearFaultRequest { code }
+    }
+}
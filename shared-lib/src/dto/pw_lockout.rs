@@ -0,0 +1,24 @@
+use super::pw_config::{Deserialize, Serialize, DTO_SIZE};
+
+/// Passenger lockout owned by the main server, sent along with every heartbeat.
+/// The door modules ignore their own window switch while it is locked.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct PassengerLockoutState {
+    pub locked: bool,
+}
+
+impl Serialize for PassengerLockoutState {
+    fn serialize(&self) -> [u8; DTO_SIZE] {
+        let mut buffer: [u8; DTO_SIZE] = [0; DTO_SIZE];
+
+        buffer[0] = self.locked as u8;
+
+        buffer
+    }
+}
+
+impl Deserialize for PassengerLockoutState {
+    fn deserialize(buffer: [u8; DTO_SIZE]) -> Self {
+        PassengerLockoutState { locked: buffer[0] != 0 }
+    }
+}
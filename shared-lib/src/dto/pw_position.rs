@@ -0,0 +1,25 @@
+use super::pw_config::{Deserialize, Serialize, DTO_SIZE};
+
+#[derive(Debug, Clone, Copy)]
+pub struct MoveToPositionRequest {
+    /// Target position in percent, 0 is fully closed, 100 is fully open
+    pub position_percent: u8,
+}
+
+impl Serialize for MoveToPositionRequest {
+    fn serialize(&self) -> [u8; DTO_SIZE] {
+        let mut buffer: [u8; DTO_SIZE] = [0; DTO_SIZE];
+
+        buffer[0] = self.position_percent;
+
+        buffer
+    }
+}
+
+impl Deserialize for MoveToPositionRequest {
+    fn deserialize(buffer: [u8; DTO_SIZE]) -> Self {
+        MoveToPositionRequest {
+            position_percent: buffer[0],
+        }
+    }
+}
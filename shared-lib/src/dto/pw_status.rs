@@ -0,0 +1,124 @@
+use crate::units::{Milliamps, Milliseconds};
+
+use super::pw_config::{Deserialize, Serialize, DTO_SIZE};
+
+const UNKNOWN_POSITION: u8 = 0xFF;
+
+/// Raw value of the door module's state while the glass travels down to the lower end stop
+pub const STATE_OPENING_FULLY: u8 = 0b0110;
+/// Raw value of the door module's state once the glass has reached the lower end stop
+pub const STATE_OPENING_FINISHED: u8 = 0b0111;
+/// Raw value of the door module's state while the glass travels up to the upper end stop
+pub const STATE_CLOSING_FULLY: u8 = 0b1110;
+/// Raw value of the door module's state once the glass has reached the upper end stop
+pub const STATE_CLOSING_FINISHED: u8 = 0b1111;
+/// Raw value of the door module's state once it has backed off an obstruction while closing
+pub const STATE_REVERSED_AFTER_OBSTRUCTION: u8 = 0b100001;
+/// Raw value of the door module's state while a critical fault keeps the window in its safe state
+pub const STATE_FAULTED: u8 = 0xFF;
+
+/// Raw values older door modules stopped at after an obstruction or stall while closing, they reverse now
+const LEGACY_STATE_CLOSING_OBSTRUCTED: u8 = 0b101011;
+const LEGACY_STATE_CLOSING_STALLED: u8 = 0b1001011;
+
+#[derive(Debug, Clone, Copy, Default)]
+pub struct PowerWindowStatus {
+    /// Raw value of the door module's window state
+    pub state: u8,
+    /// Estimated position in percent, 0 is fully closed, 100 is fully open
+    pub position_percent: Option<u8>,
+    /// Raw value of the door module's cause for the last motor stop
+    pub last_stop_cause: Option<u8>,
+    /// Highest current seen while starting the last movement
+    pub inrush_peak_current: Milliamps,
+    /// How long the current of the last movement took to settle after starting
+    pub inrush_duration: Milliseconds,
+    /// Raw value of the door module's most severe fault, active or stored
+    pub fault: Option<u8>,
+    /// Estimated motor heat in percent of the thermal limit
+    pub thermal_load_percent: u8,
+    /// Seconds until the motor has cooled down enough to move again, 0 if it may move
+    pub cooldown_secs: u16,
+    /// Raw value of the reason the door module started for
+    pub reset_reason: u8,
+    /// Raw value of the window state right before the door module was reset, if it was recorded
+    pub state_before_reset: Option<u8>,
+    /// Raw value of where the door module's last movement has been asked for
+    pub movement_source: Option<u8>,
+    /// Whether the main server has locked out the window switch on the door
+    pub passenger_lockout: bool,
+}
+
+impl Serialize for PowerWindowStatus {
+    fn serialize(&self) -> [u8; DTO_SIZE] {
+        let mut buffer: [u8; DTO_SIZE] = [0; DTO_SIZE];
+
+        buffer[0] = self.state;
+        buffer[1] = self.position_percent.unwrap_or(UNKNOWN_POSITION);
+        buffer[2] = self.last_stop_cause.unwrap_or(0);
+        buffer[3..5].copy_from_slice(&self.inrush_peak_current.to_be_bytes());
+        buffer[5..7].copy_from_slice(&self.inrush_duration.to_be_bytes());
+        buffer[7] = self.fault.unwrap_or(0);
+        buffer[8] = self.thermal_load_percent;
+        buffer[9..11].copy_from_slice(&self.cooldown_secs.to_be_bytes());
+        buffer[11] = self.reset_reason;
+        buffer[12] = self.state_before_reset.is_some() as u8;
+        buffer[13] = self.state_before_reset.unwrap_or(0);
+        buffer[14] = self.movement_source.unwrap_or(0);
+        buffer[15] = self.passenger_lockout as u8;
+
+        buffer
+    }
+}
+
+/// Maps states only older door modules report onto the one they correspond to now
+fn current_state(state: u8) -> u8 {
+    match state {
+        LEGACY_STATE_CLOSING_OBSTRUCTED | LEGACY_STATE_CLOSING_STALLED => STATE_REVERSED_AFTER_OBSTRUCTION,
+        state => state,
+    }
+}
+
+impl Deserialize for PowerWindowStatus {
+    fn deserialize(buffer: [u8; DTO_SIZE]) -> Self {
+        let position_percent = match buffer[1] {
+            UNKNOWN_POSITION => None,
+            position => Some(position),
+        };
+
+        let last_stop_cause = match buffer[2] {
+            0 => None,
+            cause => Some(cause),
+        };
+
+        let fault = match buffer[7] {
+            0 => None,
+            fault => Some(fault),
+        };
+
+        let state_before_reset = match buffer[12] {
+            0 => None,
+            _ => Some(current_state(buffer[13])),
+        };
+
+        let movement_source = match buffer[14] {
+            0 => None,
+            source => Some(source),
+        };
+
+        PowerWindowStatus {
+            state: current_state(buffer[0]),
+            position_percent,
+            last_stop_cause,
+            inrush_peak_current: Milliamps::from_be_bytes([buffer[3], buffer[4]]),
+            inrush_duration: Milliseconds::from_be_bytes([buffer[5], buffer[6]]),
+            fault,
+            thermal_load_percent: buffer[8],
+            cooldown_secs: u16::from_be_bytes([buffer[9], buffer[10]]),
+            reset_reason: buffer[11],
+            state_before_reset,
+            movement_source,
+            passenger_lockout: buffer[15] != 0,
+        }
+    }
+}
@@ -0,0 +1,32 @@
+use serde::{Deserialize, Serialize};
+
+use crate::units::{Milliamps, Milliseconds};
+
+/// Single message of the door module's telemetry stream, sent as JSON
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum TelemetryEvent {
+    /// The window has changed its state
+    State {
+        uptime_millis: u64,
+        /// Name of the window state
+        state: String,
+        /// Raw value of the window state, as in the binary status
+        state_code: u8,
+        position_percent: Option<u8>,
+    },
+    /// Filtered motor currents read by the control loop
+    Current {
+        uptime_millis: u64,
+        closing_milliamps: Milliamps,
+        opening_milliamps: Milliamps,
+        saturated: bool,
+    },
+}
+
+/// Sent by a telemetry client to change how it is streamed to
+#[derive(Debug, Clone, Copy, Serialize, Deserialize)]
+pub struct TelemetrySettings {
+    /// Minimum time between two current samples, 0 for the default
+    pub sample_interval: Milliseconds,
+}
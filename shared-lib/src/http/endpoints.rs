@@ -1,4 +1,9 @@
+pub const DOOR_STATUS_PATH: &'static str = "/state";
+pub const HEARTBEAT_PATH: &'static str = "/heartbeat";
+
 pub const CONFIGURE_WINDOWS_CURRENT_THRESHOLDS_PATH: &'static str = "/power-windows/configure-current-thresholds";
+pub const RESET_WINDOWS_CONFIG_PATH: &'static str = "/power-windows/reset-config";
+pub const CLEAR_WINDOWS_FAULT_PATH: &'static str = "/power-windows/clear-fault";
 
 pub const STOP_WINDOWS_PATH: &'static str = "/power-windows/stop";
 
@@ -6,4 +11,13 @@ pub const OPEN_WINDOWS_CONTINUOUS_PATH: &'static str = "/power-windows/open";
 pub const OPEN_WINDOWS_FULLY_PATH: &'static str = "/power-windows/open-fully";
 
 pub const CLOSE_WINDOWS_CONTINUOUS_PATH: &'static str = "/power-windows/close";
-pub const CLOSE_WINDOWS_FULLY_PATH: &'static str = "/power-windows/close-fully";
\ No newline at end of file
+pub const CLOSE_WINDOWS_FULLY_PATH: &'static str = "/power-windows/close-fully";
+
+pub const MOVE_WINDOWS_TO_POSITION_PATH: &'static str = "/power-windows/move-to-position";
+
+pub const CALIBRATE_WINDOWS_PATH: &'static str = "/power-windows/calibrate";
+
+pub const WINDOWS_STATUS_PATH: &'static str = "/power-windows/status";
+pub const WINDOWS_FAULTS_PATH: &'static str = "/power-windows/faults";
+
+pub const TELEMETRY_PATH: &'static str = "/telemetry";
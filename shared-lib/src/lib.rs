@@ -1,5 +1,10 @@
 pub mod wifi;
+#[cfg(feature = "esp")]
 pub mod system;
+#[cfg(feature = "esp")]
 pub mod hal;
 pub mod http;
-pub mod dto;
\ No newline at end of file
+pub mod dto;
+pub mod sampling;
+pub mod units;
+pub mod window_switch;
\ No newline at end of file
@@ -0,0 +1,26 @@
+use esp_idf_hal::delay::{Ets, FreeRtos};
+use esp_idf_svc::systime::EspSystemTime;
+
+use super::service::SamplingClock;
+
+/// Waits of at least this long sleep on the scheduler, shorter ones busy wait
+const SLEEP_THRESHOLD_MICROS: u32 = 10_000;
+
+pub struct EspSamplingClock;
+
+impl SamplingClock for EspSamplingClock {
+    fn now_micros(&self) -> u64 {
+        EspSystemTime {}.now().as_micros() as u64
+    }
+
+    fn delay_us(&mut self, micros: u32) {
+        if micros >= SLEEP_THRESHOLD_MICROS {
+            FreeRtos::delay_ms(micros / 1000);
+            return;
+        }
+
+        // Yield first so equal priority tasks still get to run between samples
+        FreeRtos::delay_ms(0);
+        Ets::delay_us(micros);
+    }
+}
@@ -0,0 +1,102 @@
+use super::ring_buffer::RingBuffer;
+
+/// Largest supported median window
+pub const MAX_MEDIAN_WINDOW: usize = 15;
+
+#[derive(Debug, Clone, Copy)]
+pub struct FilterConfig {
+    /// Samples the median is taken over, 1 disables the median filter
+    pub median_window: usize,
+    /// Weight of a new sample in the low-pass filter, 1.0 disables it
+    pub iir_alpha: f32,
+}
+
+impl FilterConfig {
+    /// Publishes every sample unfiltered
+    pub const PASSTHROUGH: FilterConfig = FilterConfig {
+        median_window: 1,
+        iir_alpha: 1.0,
+    };
+}
+
+impl Default for FilterConfig {
+    fn default() -> Self {
+        FilterConfig::PASSTHROUGH
+    }
+}
+
+/// Median over the last samples, removes single sample spikes
+pub struct MedianFilter {
+    window: usize,
+    samples: RingBuffer<u16, MAX_MEDIAN_WINDOW>,
+}
+
+impl MedianFilter {
+    pub fn new(window: usize) -> MedianFilter {
+        MedianFilter {
+            window: window.clamp(1, MAX_MEDIAN_WINDOW),
+            samples: RingBuffer::default(),
+        }
+    }
+
+    pub fn apply(&mut self, sample: u16) -> u16 {
+        self.samples.push(sample);
+
+        let mut window = [0; MAX_MEDIAN_WINDOW];
+        let skip = self.samples.len().saturating_sub(self.window);
+        let len = self.samples.len() - skip;
+
+        for (slot, sample) in window.iter_mut().zip(self.samples.iter().skip(skip)) {
+            *slot = sample;
+        }
+
+        let window = &mut window[..len];
+        window.sort_unstable();
+
+        window[len / 2]
+    }
+}
+
+/// First order low-pass filter
+pub struct IirFilter {
+    alpha: f32,
+    state: Option<f32>,
+}
+
+impl IirFilter {
+    pub fn new(alpha: f32) -> IirFilter {
+        IirFilter {
+            alpha: alpha.clamp(0.0, 1.0),
+            state: None,
+        }
+    }
+
+    pub fn apply(&mut self, sample: u16) -> u16 {
+        let state = match self.state {
+            Some(state) => state + (sample as f32 - state) * self.alpha,
+            None => sample as f32,
+        };
+        self.state = Some(state);
+
+        state.round() as u16
+    }
+}
+
+/// Median followed by the low-pass filter
+pub struct ChannelFilter {
+    median: MedianFilter,
+    iir: IirFilter,
+}
+
+impl ChannelFilter {
+    pub fn new(config: FilterConfig) -> ChannelFilter {
+        ChannelFilter {
+            median: MedianFilter::new(config.median_window),
+            iir: IirFilter::new(config.iir_alpha),
+        }
+    }
+
+    pub fn apply(&mut self, sample: u16) -> u16 {
+        self.iir.apply(self.median.apply(sample))
+    }
+}
@@ -0,0 +1,6 @@
+pub mod filter;
+pub mod ring_buffer;
+pub mod service;
+pub mod stream;
+#[cfg(feature = "esp")]
+pub mod esp_clock;
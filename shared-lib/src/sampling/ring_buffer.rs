@@ -0,0 +1,56 @@
+/// Fixed capacity buffer which overwrites its oldest item once full
+pub struct RingBuffer<T, const N: usize>
+where
+    T: Copy + Default,
+{
+    items: [T; N],
+    /// Index the next item is written to
+    head: usize,
+    len: usize,
+}
+
+impl<T, const N: usize> Default for RingBuffer<T, N>
+where
+    T: Copy + Default,
+{
+    fn default() -> Self {
+        RingBuffer {
+            items: [T::default(); N],
+            head: 0,
+            len: 0,
+        }
+    }
+}
+
+impl<T, const N: usize> RingBuffer<T, N>
+where
+    T: Copy + Default,
+{
+    pub fn push(&mut self, item: T) {
+        self.items[self.head] = item;
+        self.head = (self.head + 1) % N;
+        self.len = (self.len + 1).min(N);
+    }
+
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    pub fn latest(&self) -> Option<T> {
+        match self.len {
+            0 => None,
+            _ => Some(self.items[(self.head + N - 1) % N]),
+        }
+    }
+
+    /// Items from the oldest to the latest
+    pub fn iter(&self) -> impl Iterator<Item = T> + '_ {
+        let start = (self.head + N - self.len) % N;
+
+        (0..self.len).map(move |offset| self.items[(start + offset) % N])
+    }
+}
@@ -0,0 +1,227 @@
+use std::{
+    fmt::Debug,
+    sync::{
+        atomic::{AtomicU32, Ordering},
+        Arc, Mutex,
+    },
+    thread::JoinHandle,
+};
+
+use tokio::sync::{watch, Notify};
+
+use super::{
+    filter::{ChannelFilter, FilterConfig},
+    ring_buffer::RingBuffer,
+};
+
+/// Raw samples kept per channel
+pub const HISTORY_SIZE: usize = 512;
+/// Trip level of a channel which isn't armed
+const TRIP_DISARMED: u32 = u32::MAX;
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct Sample {
+    pub value: u16,
+    /// Time the sample was taken at, on the clock of the sampling service
+    pub timestamp_micros: u64,
+}
+
+/// Latest sample of a channel together with its filtered value
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct ChannelReading {
+    pub raw: Sample,
+    pub filtered: u16,
+}
+
+/// ADC channels read by the sampling service
+pub trait AdcSource: Send {
+    type Error: Debug;
+
+    fn channel_count(&self) -> usize;
+
+    fn read_channel(&mut self, channel: usize) -> Result<u16, Self::Error>;
+}
+
+pub trait SamplingClock: Send {
+    fn now_micros(&self) -> u64;
+
+    fn delay_us(&mut self, micros: u32);
+}
+
+#[derive(Debug, Clone)]
+pub struct SamplingConfig {
+    /// Time between two samples of every channel
+    pub interval_micros: u32,
+    /// Filters per channel, channels without one are passed through unfiltered
+    pub filters: Vec<FilterConfig>,
+}
+
+/// Gives access to a single channel of a running sampling service
+#[derive(Clone)]
+pub struct ChannelHandle {
+    history: Arc<Mutex<RingBuffer<Sample, HISTORY_SIZE>>>,
+    readings: watch::Receiver<ChannelReading>,
+    trip_level: Arc<AtomicU32>,
+    trip: Arc<Notify>,
+}
+
+impl ChannelHandle {
+    /// Latest reading, default until the first sample has been taken
+    pub fn latest(&self) -> ChannelReading {
+        *self.readings.borrow()
+    }
+
+    /// Receiver notified on every new reading
+    pub fn subscribe(&self) -> watch::Receiver<ChannelReading> {
+        self.readings.clone()
+    }
+
+    /// Raw samples taken after the given time, oldest first
+    pub fn samples_since(&self, since_micros: u64, samples: &mut Vec<Sample>) {
+        let history = self.history.lock().expect("Sample history poisoned");
+
+        samples.extend(history.iter().filter(|sample| sample.timestamp_micros > since_micros));
+    }
+
+    /// Notifies the trip signal as soon as a filtered reading exceeds `level`, `None` disarms.
+    /// The comparison runs as the samples are taken, a trip disarms the channel until it's armed again.
+    pub fn arm_trip(&self, level: Option<u16>) {
+        self.trip_level
+            .store(level.map_or(TRIP_DISARMED, u32::from), Ordering::Release);
+    }
+
+    /// Signal shared by all channels of the service, notified whenever one of them trips
+    pub fn trip_signal(&self) -> Arc<Notify> {
+        self.trip.clone()
+    }
+}
+
+pub(super) struct Channel {
+    filter: ChannelFilter,
+    history: Arc<Mutex<RingBuffer<Sample, HISTORY_SIZE>>>,
+    readings: watch::Sender<ChannelReading>,
+    trip_level: Arc<AtomicU32>,
+}
+
+impl Channel {
+    pub(super) fn new(filter: FilterConfig) -> Channel {
+        Channel {
+            filter: ChannelFilter::new(filter),
+            history: Arc::new(Mutex::new(RingBuffer::default())),
+            readings: watch::channel(ChannelReading::default()).0,
+            trip_level: Arc::new(AtomicU32::new(TRIP_DISARMED)),
+        }
+    }
+
+    pub(super) fn handle(&self, trip: &Arc<Notify>) -> ChannelHandle {
+        ChannelHandle {
+            history: self.history.clone(),
+            readings: self.readings.subscribe(),
+            trip_level: self.trip_level.clone(),
+            trip: trip.clone(),
+        }
+    }
+
+    /// Keeps a raw sample, publishes its filtered reading and trips if the reading exceeds the armed level
+    pub(super) fn record(&mut self, raw: Sample, trip: &Notify) {
+        self.history.lock().expect("Sample history poisoned").push(raw);
+
+        let filtered = self.filter.apply(raw.value);
+        self.readings.send_replace(ChannelReading { raw, filtered });
+
+        let level = self.trip_level.load(Ordering::Acquire);
+
+        // Only the sample disarming the level notifies, re-arming races with at most one more trip
+        if level != TRIP_DISARMED
+            && u32::from(filtered) > level
+            && self
+                .trip_level
+                .compare_exchange(level, TRIP_DISARMED, Ordering::AcqRel, Ordering::Acquire)
+                .is_ok()
+        {
+            trip.notify_one();
+        }
+    }
+}
+
+/// Reads all channels of an ADC source at a fixed rate, keeping the raw
+/// samples in ring buffers and publishing filtered readings, so consumers
+/// never have to touch the ADC themselves.
+pub struct SamplingService<TSource, TClock>
+where
+    TSource: AdcSource,
+    TClock: SamplingClock,
+{
+    source: TSource,
+    clock: TClock,
+    interval_micros: u32,
+    channels: Vec<Channel>,
+    trip: Arc<Notify>,
+}
+
+impl<TSource, TClock> SamplingService<TSource, TClock>
+where
+    TSource: AdcSource,
+    TClock: SamplingClock,
+{
+    pub fn new(source: TSource, clock: TClock, config: SamplingConfig) -> SamplingService<TSource, TClock> {
+        let channels = (0..source.channel_count())
+            .map(|channel| Channel::new(config.filters.get(channel).copied().unwrap_or_default()))
+            .collect();
+
+        SamplingService {
+            source,
+            clock,
+            interval_micros: config.interval_micros.max(1),
+            channels,
+            trip: Arc::new(Notify::new()),
+        }
+    }
+
+    pub fn channel(&self, channel: usize) -> ChannelHandle {
+        self.channels[channel].handle(&self.trip)
+    }
+
+    /// Takes one sample of every channel
+    pub fn sample_once(&mut self) -> Result<(), TSource::Error> {
+        for (index, channel) in self.channels.iter_mut().enumerate() {
+            let value = self.source.read_channel(index)?;
+            let raw = Sample {
+                value,
+                timestamp_micros: self.clock.now_micros(),
+            };
+
+            channel.record(raw, &self.trip);
+        }
+
+        Ok(())
+    }
+
+    /// Samples forever, keeping the configured rate as long as reading the channels takes less than the interval
+    pub fn run(mut self) {
+        loop {
+            let started_micros = self.clock.now_micros();
+
+            if let Err(err) = self.sample_once() {
+                log::error!("Couldn't sample ADC channels: {:?}", err);
+            }
+
+            let elapsed_micros = self.clock.now_micros().saturating_sub(started_micros);
+            let remaining_micros = (self.interval_micros as u64).saturating_sub(elapsed_micros);
+
+            self.clock.delay_us(remaining_micros as u32);
+        }
+    }
+
+    /// Runs the service on its own thread
+    pub fn spawn(self, stack_size: usize) -> std::io::Result<JoinHandle<()>>
+    where
+        TSource: 'static,
+        TClock: 'static,
+    {
+        std::thread::Builder::new()
+            .name("adc-sampling".to_string())
+            .stack_size(stack_size)
+            .spawn(move || self.run())
+    }
+}
@@ -0,0 +1,137 @@
+use std::{fmt::Debug, sync::Arc, thread::JoinHandle};
+
+use tokio::sync::Notify;
+
+use super::{
+    filter::FilterConfig,
+    service::{Channel, ChannelHandle, Sample, SamplingClock},
+};
+
+/// Time to wait after a failed read before reading the stream again
+const ERROR_BACKOFF_MICROS: u32 = 10_000;
+
+/// Sample of a single channel taken by a continuously converting ADC
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct StreamSample {
+    pub channel: usize,
+    pub value: u16,
+}
+
+/// ADC converting its channels one after another on its own, delivering
+/// the samples in frames instead of being read sample by sample
+pub trait AdcStream: Send {
+    type Error: Debug;
+
+    fn channel_count(&self) -> usize;
+
+    /// Time between two samples of the same channel
+    fn sample_interval_micros(&self) -> u32;
+
+    /// Blocks until the next frame has been converted, appending its samples oldest first
+    fn read_frame(&mut self, samples: &mut Vec<StreamSample>) -> Result<(), Self::Error>;
+}
+
+/// Sampling service fed by a continuously converting ADC. Every sample of a
+/// frame is kept, filtered and compared against the armed trip levels as
+/// soon as the frame arrives, so the reaction time is bounded by the frame
+/// length instead of by how often a consumer polls.
+pub struct StreamSamplingService<TStream, TClock>
+where
+    TStream: AdcStream,
+    TClock: SamplingClock,
+{
+    stream: TStream,
+    clock: TClock,
+    channels: Vec<Channel>,
+    trip: Arc<Notify>,
+    frame: Vec<StreamSample>,
+    /// Timestamp of the latest sample per channel
+    latest_micros: Vec<u64>,
+    /// Samples per channel of the frame being recorded which are still to come
+    remaining: Vec<u64>,
+}
+
+impl<TStream, TClock> StreamSamplingService<TStream, TClock>
+where
+    TStream: AdcStream,
+    TClock: SamplingClock,
+{
+    /// Creates the service, channels without a filter are passed through unfiltered
+    pub fn new(stream: TStream, clock: TClock, filters: &[FilterConfig]) -> StreamSamplingService<TStream, TClock> {
+        let channel_count = stream.channel_count();
+
+        StreamSamplingService {
+            stream,
+            clock,
+            channels: (0..channel_count)
+                .map(|channel| Channel::new(filters.get(channel).copied().unwrap_or_default()))
+                .collect(),
+            trip: Arc::new(Notify::new()),
+            frame: Vec::new(),
+            latest_micros: vec![0; channel_count],
+            remaining: vec![0; channel_count],
+        }
+    }
+
+    pub fn channel(&self, channel: usize) -> ChannelHandle {
+        self.channels[channel].handle(&self.trip)
+    }
+
+    /// Waits for the next frame and records its samples. They are timestamped
+    /// back from the arrival of the frame, one sample interval apart per channel.
+    pub fn sample_frame(&mut self) -> Result<(), TStream::Error> {
+        self.frame.clear();
+        self.stream.read_frame(&mut self.frame)?;
+
+        let arrived_micros = self.clock.now_micros();
+        let interval_micros = self.stream.sample_interval_micros().max(1) as u64;
+
+        let channel_count = self.channels.len();
+        self.remaining.fill(0);
+        for sample in self.frame.iter().filter(|sample| sample.channel < channel_count) {
+            self.remaining[sample.channel] += 1;
+        }
+
+        for sample in self.frame.iter().filter(|sample| sample.channel < channel_count) {
+            self.remaining[sample.channel] -= 1;
+
+            // Never older than the previous sample, a late frame mustn't reorder the history
+            let timestamp_micros = arrived_micros
+                .saturating_sub(self.remaining[sample.channel] * interval_micros)
+                .max(self.latest_micros[sample.channel] + 1);
+            self.latest_micros[sample.channel] = timestamp_micros;
+
+            self.channels[sample.channel].record(
+                Sample {
+                    value: sample.value,
+                    timestamp_micros,
+                },
+                &self.trip,
+            );
+        }
+
+        Ok(())
+    }
+
+    /// Samples forever, backing off for a moment whenever the stream fails
+    pub fn run(mut self) {
+        loop {
+            if let Err(err) = self.sample_frame() {
+                log::error!("Couldn't read ADC stream: {:?}", err);
+                self.clock.delay_us(ERROR_BACKOFF_MICROS);
+            }
+        }
+    }
+
+    /// Runs the service on its own thread
+    pub fn spawn(self, stack_size: usize) -> std::io::Result<JoinHandle<()>>
+    where
+        TStream: 'static,
+        TClock: 'static,
+    {
+        std::thread::Builder::new()
+            .name("adc-stream".to_string())
+            .stack_size(stack_size)
+            .spawn(move || self.run())
+    }
+}
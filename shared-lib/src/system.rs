@@ -1,6 +1,11 @@
 use esp_idf_sys::esp;
 use futures::Future;
 
+/// Stack of the threads running blocking tasks, the HTTP client needs more than the default
+const BLOCKING_THREAD_STACK_SIZE: usize = 8192;
+/// Blocking tasks kept running at once, each thread takes up its whole stack
+const MAX_BLOCKING_THREADS: usize = 4;
+
 pub fn setup_system() -> anyhow::Result<()>  {
     // setup
     esp_idf_svc::sys::link_patches();
@@ -23,6 +28,8 @@ where
     F: Future {
     Ok(tokio::runtime::Builder::new_current_thread()
         .enable_all()
+        .thread_stack_size(BLOCKING_THREAD_STACK_SIZE)
+        .max_blocking_threads(MAX_BLOCKING_THREADS)
         .build()?
         .block_on(future))
 }
\ No newline at end of file
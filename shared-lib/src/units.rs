@@ -0,0 +1,128 @@
+use std::{fmt, time::Duration};
+
+use serde::{Deserialize, Serialize};
+
+macro_rules! unit {
+    ($(#[$meta:meta])* $name:ident, $symbol:literal) => {
+        $(#[$meta])*
+        #[derive(Debug, Clone, Copy, Default, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize, Deserialize)]
+        #[serde(transparent)]
+        pub struct $name(pub u16);
+
+        impl $name {
+            pub const ZERO: $name = $name(0);
+            pub const MAX: $name = $name(u16::MAX);
+
+            pub fn to_be_bytes(self) -> [u8; 2] {
+                self.0.to_be_bytes()
+            }
+
+            pub fn from_be_bytes(bytes: [u8; 2]) -> $name {
+                $name(u16::from_be_bytes(bytes))
+            }
+
+            /// Replaces zero, which older senders leave in fields they don't know, with `default`
+            pub fn non_zero_or(self, default: $name) -> $name {
+                match self.0 {
+                    0 => default,
+                    _ => self,
+                }
+            }
+
+            pub fn saturating_add(self, other: $name) -> $name {
+                $name(self.0.saturating_add(other.0))
+            }
+
+            pub fn saturating_sub(self, other: $name) -> $name {
+                $name(self.0.saturating_sub(other.0))
+            }
+
+            /// Halfway between `self` and `other`
+            pub fn midpoint(self, other: $name) -> $name {
+                $name(((self.0 as u32 + other.0 as u32) / 2) as u16)
+            }
+        }
+
+        impl fmt::Display for $name {
+            fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+                write!(f, "{}{}", self.0, $symbol)
+            }
+        }
+    };
+}
+
+unit!(
+    /// Current in mA
+    Milliamps,
+    "mA"
+);
+unit!(
+    /// Voltage in mV
+    Millivolts,
+    "mV"
+);
+unit!(
+    /// Duration in ms
+    Milliseconds,
+    "ms"
+);
+
+impl Milliseconds {
+    pub fn as_millis(self) -> u128 {
+        self.0 as u128
+    }
+
+    /// Converts a longer duration, `None` if it doesn't fit
+    pub fn checked_from_millis(millis: u128) -> Option<Milliseconds> {
+        u16::try_from(millis).ok().map(Milliseconds)
+    }
+}
+
+impl From<Milliseconds> for Duration {
+    fn from(millis: Milliseconds) -> Self {
+        Duration::from_millis(millis.0 as u64)
+    }
+}
+
+/// Gain of a current sense amplifier in mV/A
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(transparent)]
+pub struct MillivoltsPerAmp(pub u16);
+
+impl MillivoltsPerAmp {
+    pub fn to_be_bytes(self) -> [u8; 2] {
+        self.0.to_be_bytes()
+    }
+
+    pub fn from_be_bytes(bytes: [u8; 2]) -> MillivoltsPerAmp {
+        MillivoltsPerAmp(u16::from_be_bytes(bytes))
+    }
+
+    /// Replaces zero, which older senders leave in fields they don't know, with `default`
+    pub fn non_zero_or(self, default: MillivoltsPerAmp) -> MillivoltsPerAmp {
+        match self.0 {
+            0 => default,
+            _ => self,
+        }
+    }
+
+    /// Current causing the given sense voltage, `None` without a gain or if it doesn't fit
+    pub fn checked_current(self, voltage: Millivolts) -> Option<Milliamps> {
+        let milliamps = (voltage.0 as u32 * 1000).checked_div(self.0 as u32)?;
+
+        u16::try_from(milliamps).ok().map(Milliamps)
+    }
+
+    /// Sense voltage caused by the given current, `None` if it doesn't fit
+    pub fn checked_voltage(self, current: Milliamps) -> Option<Millivolts> {
+        let millivolts = current.0 as u32 * self.0 as u32 / 1000;
+
+        u16::try_from(millivolts).ok().map(Millivolts)
+    }
+}
+
+impl fmt::Display for MillivoltsPerAmp {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}mV/A", self.0)
+    }
+}
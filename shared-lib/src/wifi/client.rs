@@ -11,10 +11,14 @@ use esp_idf_svc::{eventloop::EspSystemEventLoop, nvs::EspDefaultNvsPartition};
 use futures::executor::block_on;
 use log::info;
 
-pub fn connect_wifi_sync(modem: Modem, ssid: &'static str, password: &'static str) -> Result<AsyncWifi<EspWifi<'static>>> {
+pub fn connect_wifi_sync(
+    modem: Modem,
+    nvs: EspDefaultNvsPartition,
+    ssid: &'static str,
+    password: &'static str,
+) -> Result<AsyncWifi<EspWifi<'static>>> {
     let sys_loop = EspSystemEventLoop::take()?;
     let timer_service = EspTaskTimerService::new()?;
-    let nvs = EspDefaultNvsPartition::take()?;
 
     let mut wifi = AsyncWifi::wrap(
         EspWifi::new(modem, sys_loop.clone(), Some(nvs))?,
@@ -11,11 +11,7 @@ use esp_idf_sys::{
 
 use super::mac::MacAddress;
 
-#[derive(Debug, Clone, Copy)]
-pub struct ApClientInfo {
-    pub mac: MacAddress,
-    pub ip: Ipv4Addr,
-}
+pub use super::types::ApClientInfo;
 
 pub fn get_sta_mac_address(
     wifi: &mut AsyncWifi<EspWifi<'static>>,
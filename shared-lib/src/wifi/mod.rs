@@ -1,5 +1,9 @@
+#[cfg(feature = "esp")]
 pub mod client;
+#[cfg(feature = "esp")]
 pub mod server;
 pub mod config;
+#[cfg(feature = "esp")]
 pub mod ext;
-pub mod mac;
\ No newline at end of file
+pub mod mac;
+pub mod types;
\ No newline at end of file
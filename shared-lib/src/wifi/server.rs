@@ -10,12 +10,12 @@ use log::info;
 
 pub fn create_wifi_ap_sync(
     modem: Modem,
+    nvs: EspDefaultNvsPartition,
     ssid: &'static str,
     password: &'static str,
 ) -> Result<AsyncWifi<EspWifi<'static>>> {
     let sys_loop = EspSystemEventLoop::take()?;
     let timer_service = EspTaskTimerService::new()?;
-    let nvs = EspDefaultNvsPartition::take()?;
 
     let mut wifi = AsyncWifi::wrap(
         EspWifi::new(modem, sys_loop.clone(), Some(nvs))?,
@@ -0,0 +1,9 @@
+use std::net::Ipv4Addr;
+
+use super::mac::MacAddress;
+
+#[derive(Debug, Clone, Copy)]
+pub struct ApClientInfo {
+    pub mac: MacAddress,
+    pub ip: Ipv4Addr,
+}
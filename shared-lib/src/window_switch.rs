@@ -0,0 +1,40 @@
+use crate::units::Millivolts;
+
+const VOLTAGE_CONTINOUS_THRESHOLD: Millivolts = Millivolts(300);
+const VOLTAGE_FULL_THRESHOLD: Millivolts = Millivolts(700);
+
+/// Stage of a two-stage window switch, the second stage moves the window all the way
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PowerWindowButtonState {
+    None = 0,
+    OpenContinuous = 0b001,
+    CloseContinuous = 0b010,
+    OpenFully = 0b101,
+    CloseFully = 0b110,
+}
+
+/// Classifies the two-stage button ladder voltages into a button state
+pub fn get_state_for_voltages(open: Millivolts, close: Millivolts) -> PowerWindowButtonState {
+    if open > VOLTAGE_CONTINOUS_THRESHOLD && close > VOLTAGE_CONTINOUS_THRESHOLD {
+        log::warn!("Both buttons are pressed at the same time!");
+        return PowerWindowButtonState::None;
+    }
+
+    if open > VOLTAGE_CONTINOUS_THRESHOLD {
+        if open > VOLTAGE_FULL_THRESHOLD {
+            return PowerWindowButtonState::OpenFully;
+        } else {
+            return PowerWindowButtonState::OpenContinuous;
+        }
+    }
+
+    if close > VOLTAGE_CONTINOUS_THRESHOLD {
+        if close > VOLTAGE_FULL_THRESHOLD {
+            return PowerWindowButtonState::CloseFully;
+        } else {
+            return PowerWindowButtonState::CloseContinuous;
+        }
+    }
+
+    PowerWindowButtonState::None
+}